@@ -0,0 +1,162 @@
+//! Compares the order book with the previous `Vec` based book.
+//!
+//! Each benchmark starts from a book of 100k resting orders and measures a
+//! batch of operations against it.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use matching_engine::{OrderBook, OrderId, Owner, Word};
+use vec_book::VecBook;
+
+mod vec_book;
+
+/// The number of orders resting in the book.
+const RESTING: usize = 100_000;
+/// The number of operations measured per iteration.
+const BATCH: usize = 1_000;
+/// The number of distinct prices on each side.
+const LEVELS: u64 = 1_000;
+/// Bids rest below this price and asks at or above it.
+const MID: Word = 10_000;
+
+const OWNER: Owner = [1, 1, 1, 1];
+
+/// A small deterministic generator, so both books see the same orders.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A price below `MID` for a bid or at or above it for an ask.
+    fn price(&mut self, bid: bool) -> Word {
+        let offset = (self.next() % LEVELS) as Word;
+        if bid {
+            MID - 1 - offset
+        } else {
+            MID + offset
+        }
+    }
+}
+
+/// The operations both books are benchmarked with.
+trait Book: Clone {
+    fn add_bid(&mut self, price: Word, quantity: Word) -> OrderId;
+    fn add_ask(&mut self, price: Word, quantity: Word) -> OrderId;
+    fn cancel(&mut self, id: OrderId);
+    fn match_orders(&mut self) -> usize;
+}
+
+impl Book for OrderBook<()> {
+    fn add_bid(&mut self, price: Word, quantity: Word) -> OrderId {
+        OrderBook::add_bid(self, OWNER, price, quantity, ())
+    }
+    fn add_ask(&mut self, price: Word, quantity: Word) -> OrderId {
+        OrderBook::add_ask(self, OWNER, price, quantity, ())
+    }
+    fn cancel(&mut self, id: OrderId) {
+        OrderBook::cancel(self, id);
+    }
+    fn match_orders(&mut self) -> usize {
+        OrderBook::match_orders(self).len()
+    }
+}
+
+impl Book for VecBook<()> {
+    fn add_bid(&mut self, price: Word, quantity: Word) -> OrderId {
+        VecBook::add_bid(self, OWNER, price, quantity, ())
+    }
+    fn add_ask(&mut self, price: Word, quantity: Word) -> OrderId {
+        VecBook::add_ask(self, OWNER, price, quantity, ())
+    }
+    fn cancel(&mut self, id: OrderId) {
+        VecBook::cancel(self, id);
+    }
+    fn match_orders(&mut self) -> usize {
+        VecBook::match_orders(self).len()
+    }
+}
+
+/// Fills a book with resting bids and asks that do not cross.
+fn resting<B: Book>(mut book: B) -> (B, Vec<OrderId>) {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let ids = (0..RESTING)
+        .map(|i| {
+            let bid = i % 2 == 0;
+            let price = rng.price(bid);
+            if bid {
+                book.add_bid(price, 10)
+            } else {
+                book.add_ask(price, 10)
+            }
+        })
+        .collect();
+    (book, ids)
+}
+
+fn bench<B: Book>(c: &mut Criterion, name: &str, book: B) {
+    let (book, ids) = resting(book);
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+
+    group.bench_function("add", |b| {
+        b.iter_batched(
+            || book.clone(),
+            |mut book| {
+                let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+                for i in 0..BATCH {
+                    let bid = i % 2 == 0;
+                    let price = rng.price(bid);
+                    if bid {
+                        book.add_bid(price, 10);
+                    } else {
+                        book.add_ask(price, 10);
+                    }
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("cancel", |b| {
+        let cancels: Vec<_> = ids.iter().step_by(RESTING / BATCH).copied().collect();
+        b.iter_batched(
+            || book.clone(),
+            |mut book| {
+                for id in &cancels {
+                    book.cancel(*id);
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("match", |b| {
+        b.iter_batched(
+            || book.clone(),
+            |mut book| {
+                // Each bid takes out the front of the asks
+                for _ in 0..BATCH {
+                    book.add_bid(MID + LEVELS as Word, 10);
+                    book.match_orders();
+                }
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn books(c: &mut Criterion) {
+    bench(c, "btree_book", OrderBook::<()>::new());
+    bench(c, "vec_book", VecBook::<()>::default());
+}
+
+criterion_group!(benches, books);
+criterion_main!(benches);
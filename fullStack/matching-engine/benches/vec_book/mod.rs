@@ -0,0 +1,91 @@
+//! The previous order book, kept as a baseline for the benchmarks.
+//!
+//! Each side is a `Vec` sorted by price then time. Adding pushes the order and
+//! re-sorts the whole side, filling removes from the front and cancelling scans
+//! the whole side.
+//!
+//! This lives in `vec_book/mod.rs` so cargo does not take it for a bench target.
+
+use std::cmp::Reverse;
+
+use matching_engine::{Fill, Order, OrderId, Owner, Side, Word};
+
+#[derive(Clone, Default)]
+pub struct VecBook<T> {
+    bids: Vec<Order<T>>,
+    asks: Vec<Order<T>>,
+    next_id: OrderId,
+}
+
+impl<T: Clone> VecBook<T> {
+    pub fn add_bid(&mut self, owner: Owner, price: Word, quantity: Word, data: T) -> OrderId {
+        let order = self.order(Side::Bid, owner, price, quantity, data);
+        let id = order.id;
+        self.bids.push(order);
+        // A stable sort keeps orders at the same price in time order
+        self.bids.sort_by_key(|b| Reverse(b.price));
+        id
+    }
+
+    pub fn add_ask(&mut self, owner: Owner, price: Word, quantity: Word, data: T) -> OrderId {
+        let order = self.order(Side::Ask, owner, price, quantity, data);
+        let id = order.id;
+        self.asks.push(order);
+        self.asks.sort_by_key(|a| a.price);
+        id
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Option<Order<T>> {
+        for side in [&mut self.bids, &mut self.asks] {
+            if let Some(ix) = side.iter().position(|o| o.id == id) {
+                return Some(side.remove(ix));
+            }
+        }
+        None
+    }
+
+    pub fn match_orders(&mut self) -> Vec<Fill<T>> {
+        let mut fills = Vec::new();
+        while let (Some(bid), Some(ask)) = (self.bids.first_mut(), self.asks.first_mut()) {
+            if bid.price < ask.price {
+                break;
+            }
+            let quantity = bid.quantity.min(ask.quantity);
+            fills.push(Fill {
+                bid: bid.clone(),
+                ask: ask.clone(),
+                quantity,
+            });
+            bid.quantity -= quantity;
+            ask.quantity -= quantity;
+            if bid.quantity == 0 {
+                self.bids.remove(0);
+            }
+            if ask.quantity == 0 {
+                self.asks.remove(0);
+            }
+        }
+        fills
+    }
+
+    fn order(
+        &mut self,
+        side: Side,
+        owner: Owner,
+        price: Word,
+        quantity: Word,
+        data: T,
+    ) -> Order<T> {
+        let id = self.next_id;
+        self.next_id += 1;
+        Order {
+            id,
+            sequence: id,
+            side,
+            owner,
+            price,
+            quantity,
+            data,
+        }
+    }
+}
@@ -0,0 +1,441 @@
+#![deny(missing_docs)]
+//! # Matching Engine
+//! Matches bids against asks for the order book.
+//!
+//! Prices and quantities are integer words, the same as the order contract.
+//! Each order carries a payload of type `T` that the engine never inspects,
+//! such as the addresses of the order contract instance.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+};
+
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+/// A word, the same as the order contract's `int`.
+pub type Word = i64;
+
+/// The hashed key of an order owner.
+pub type Owner = [Word; 4];
+
+/// Uniquely identifies an order in a book.
+pub type OrderId = u64;
+
+/// The position of an order in time.
+/// Orders at the same price fill in sequence order.
+pub type Sequence = u64;
+
+/// Which side of the book an order is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    /// Buys token A.
+    Bid,
+    /// Sells token A.
+    Ask,
+}
+
+/// An order resting in the book.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Order<T> {
+    /// The ID the book assigned the order.
+    pub id: OrderId,
+    /// When the order entered the book.
+    pub sequence: Sequence,
+    /// Which side of the book the order is on.
+    pub side: Side,
+    /// The owner of the order.
+    pub owner: Owner,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The amount of token A left to fill.
+    pub quantity: Word,
+    /// Data the engine carries with the order.
+    /// Its fields are flattened into the order when serialized.
+    #[serde(flatten)]
+    pub data: T,
+}
+
+/// A bid matched against an ask.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fill<T> {
+    /// The bid as it was before the fill.
+    pub bid: Order<T>,
+    /// The ask as it was before the fill.
+    pub ask: Order<T>,
+    /// The amount of token A that was filled.
+    pub quantity: Word,
+}
+
+/// The orders resting at one price, aggregated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceLevel {
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The total quantity left to fill at the price.
+    pub quantity: Word,
+    /// The number of orders at the price.
+    pub orders: usize,
+}
+
+/// Why an order could not be cancelled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CancelError {
+    /// No order with the ID is in the book.
+    NotFound(OrderId),
+    /// The order belongs to someone else.
+    NotOwner(OrderId),
+}
+
+/// The orders resting at one price.
+#[derive(Clone, Debug, Default)]
+struct Level {
+    /// Order IDs, oldest first.
+    /// Cancelled orders are left in place and dropped when they reach the front.
+    queue: VecDeque<OrderId>,
+    /// The total quantity of the live orders.
+    quantity: Word,
+    /// The number of live orders.
+    orders: usize,
+}
+
+/// The bids and asks of a market.
+///
+/// Orders have strict price-time priority: the best price fills first and
+/// orders at the same price fill oldest first.
+///
+/// Each side is a map of price levels, each holding a FIFO queue of order IDs.
+/// Orders are indexed by ID, so adding and cancelling are `O(log n)`.
+#[derive(Clone, Debug)]
+pub struct OrderBook<T> {
+    /// Bid levels by price. The best bid is the last.
+    bids: BTreeMap<Word, Level>,
+    /// Ask levels by price. The best ask is the first.
+    asks: BTreeMap<Word, Level>,
+    /// Every live order by ID.
+    orders: HashMap<OrderId, Order<T>>,
+    /// The ID given to the next order.
+    next_id: OrderId,
+    /// The sequence given to the next order.
+    next_sequence: Sequence,
+}
+
+impl<T> Default for OrderBook<T> {
+    fn default() -> Self {
+        Self {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            orders: HashMap::new(),
+            next_id: 0,
+            next_sequence: 0,
+        }
+    }
+}
+
+impl<T: Clone> OrderBook<T> {
+    /// Creates an empty book.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuilds a book from its orders and counters, such as after a restart.
+    ///
+    /// Orders keep their IDs and priority. The counters must be past every
+    /// ID and sequence ever given out, so removed orders' IDs are never reused.
+    pub fn restore(
+        orders: impl IntoIterator<Item = Order<T>>,
+        next_id: OrderId,
+        next_sequence: Sequence,
+    ) -> Self {
+        let mut orders: Vec<_> = orders.into_iter().collect();
+        orders.sort_by_key(|order| order.sequence);
+        let mut book = Self::new();
+        for order in orders {
+            let level = book.levels_mut(order.side).entry(order.price).or_default();
+            level.queue.push_back(order.id);
+            level.quantity += order.quantity;
+            level.orders += 1;
+            book.next_id = book.next_id.max(order.id + 1);
+            book.next_sequence = book.next_sequence.max(order.sequence + 1);
+            book.orders.insert(order.id, order);
+        }
+        book.next_id = book.next_id.max(next_id);
+        book.next_sequence = book.next_sequence.max(next_sequence);
+        book
+    }
+
+    /// Adds a bid and returns its ID.
+    pub fn add_bid(&mut self, owner: Owner, price: Word, quantity: Word, data: T) -> OrderId {
+        self.add(Side::Bid, owner, price, quantity, data)
+    }
+
+    /// Adds an ask and returns its ID.
+    pub fn add_ask(&mut self, owner: Owner, price: Word, quantity: Word, data: T) -> OrderId {
+        self.add(Side::Ask, owner, price, quantity, data)
+    }
+
+    /// Matches the best bid against the best ask until the book no longer crosses.
+    ///
+    /// Filled orders are removed from the book and partially filled orders
+    /// are left with the remaining quantity, keeping their priority.
+    pub fn match_orders(&mut self) -> Vec<Fill<T>> {
+        let mut fills = Vec::new();
+        while let (Some(bid), Some(ask)) = (self.best_bid(), self.best_ask()) {
+            // No more profitable trades can be made
+            if bid < ask {
+                break;
+            }
+            let bid = self.front(Side::Bid, bid);
+            let ask = self.front(Side::Ask, ask);
+            let quantity = self.orders[&bid].quantity.min(self.orders[&ask].quantity);
+            fills.push(Fill {
+                bid: self.orders[&bid].clone(),
+                ask: self.orders[&ask].clone(),
+                quantity,
+            });
+            self.fill(bid, quantity);
+            self.fill(ask, quantity);
+        }
+        fills
+    }
+
+    /// Takes a filled quantity off an order, removing it once nothing is left.
+    ///
+    /// Unlike [`OrderBook::match_orders`] this works on any order, so fills
+    /// can be applied once they are settled elsewhere.
+    /// Returns the order as it was before, if it was in the book.
+    pub fn reduce(&mut self, id: OrderId, quantity: Word) -> Option<Order<T>> {
+        let order = self.orders.get_mut(&id)?;
+        let before = order.clone();
+        let quantity = quantity.min(order.quantity);
+        order.quantity -= quantity;
+        if order.quantity == 0 {
+            self.orders.remove(&id);
+        }
+        let levels = self.levels_mut(before.side);
+        let level = levels.get_mut(&before.price).expect("Level must exist");
+        level.quantity -= quantity;
+        if before.quantity == quantity {
+            level.orders -= 1;
+            if level.orders == 0 {
+                levels.remove(&before.price);
+            }
+        }
+        Some(before)
+    }
+}
+
+impl<T> OrderBook<T> {
+    /// The order with the ID, if it is in the book.
+    pub fn get(&self, id: OrderId) -> Option<&Order<T>> {
+        self.orders.get(&id)
+    }
+
+    /// The ID the next order will be given.
+    pub fn next_id(&self) -> OrderId {
+        self.next_id
+    }
+
+    /// The sequence the next order will be given.
+    pub fn next_sequence(&self) -> Sequence {
+        self.next_sequence
+    }
+
+    /// The number of orders in the book.
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Whether the book has no orders.
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// The highest bid price.
+    pub fn best_bid(&self) -> Option<Word> {
+        self.bids.last_key_value().map(|(price, _)| *price)
+    }
+
+    /// The lowest ask price.
+    pub fn best_ask(&self) -> Option<Word> {
+        self.asks.first_key_value().map(|(price, _)| *price)
+    }
+
+    /// Bids in priority order.
+    pub fn bids(&self) -> impl Iterator<Item = &Order<T>> + '_ {
+        self.bids
+            .values()
+            .rev()
+            .flat_map(move |level| self.live(level))
+    }
+
+    /// Asks in priority order.
+    pub fn asks(&self) -> impl Iterator<Item = &Order<T>> + '_ {
+        self.asks.values().flat_map(move |level| self.live(level))
+    }
+
+    /// Bid levels, best price first.
+    pub fn bid_levels(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.bids.iter().rev().map(PriceLevel::new)
+    }
+
+    /// Ask levels, best price first.
+    pub fn ask_levels(&self) -> impl Iterator<Item = PriceLevel> + '_ {
+        self.asks.iter().map(PriceLevel::new)
+    }
+
+    /// The level at the price, if any orders rest there.
+    pub fn level(&self, side: Side, price: Word) -> Option<PriceLevel> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+        .get_key_value(&price)
+        .map(PriceLevel::new)
+    }
+
+    /// Whether an order of the owner on the side at the price would cross one
+    /// of the owner's resting orders, and so trade with the owner.
+    pub fn crosses_own(&self, side: Side, owner: Owner, price: Word) -> bool {
+        let owned = |level| self.live(level).any(|order| order.owner == owner);
+        match side {
+            Side::Bid => self.asks.range(..=price).any(|(_, level)| owned(level)),
+            Side::Ask => self.bids.range(price..).any(|(_, level)| owned(level)),
+        }
+    }
+
+    /// Removes an order from the book.
+    ///
+    /// Returns the order if it was in the book.
+    pub fn cancel(&mut self, id: OrderId) -> Option<Order<T>> {
+        let order = self.orders.remove(&id)?;
+        let levels = self.levels_mut(order.side);
+        let level = levels
+            .get_mut(&order.price)
+            .expect("Every live order has a level");
+        level.quantity -= order.quantity;
+        level.orders -= 1;
+        if level.orders == 0 {
+            levels.remove(&order.price);
+        }
+        Some(order)
+    }
+
+    /// Removes an order from the book on behalf of its owner.
+    ///
+    /// Fails if the order is not in the book or belongs to someone else.
+    pub fn cancel_owned(&mut self, id: OrderId, owner: Owner) -> Result<Order<T>, CancelError> {
+        let order = self.orders.get(&id).ok_or(CancelError::NotFound(id))?;
+        if order.owner != owner {
+            return Err(CancelError::NotOwner(id));
+        }
+        Ok(self.cancel(id).expect("Order must exist"))
+    }
+
+    /// Adds an order to the back of its price level.
+    fn add(&mut self, side: Side, owner: Owner, price: Word, quantity: Word, data: T) -> OrderId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        let level = self.levels_mut(side).entry(price).or_default();
+        level.queue.push_back(id);
+        level.quantity += quantity;
+        level.orders += 1;
+        self.orders.insert(
+            id,
+            Order {
+                id,
+                sequence,
+                side,
+                owner,
+                price,
+                quantity,
+                data,
+            },
+        );
+        id
+    }
+
+    /// The oldest live order at the price, dropping cancelled orders ahead of it.
+    fn front(&mut self, side: Side, price: Word) -> OrderId {
+        let level = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+        .get_mut(&price)
+        .expect("Level must exist");
+        loop {
+            let id = *level.queue.front().expect("Level must have a live order");
+            if self.orders.contains_key(&id) {
+                return id;
+            }
+            level.queue.pop_front();
+        }
+    }
+
+    /// Fills the order at the front of its level.
+    fn fill(&mut self, id: OrderId, quantity: Word) {
+        let order = self.orders.get_mut(&id).expect("Order must exist");
+        order.quantity -= quantity;
+        let (side, price, filled) = (order.side, order.price, order.quantity == 0);
+        if filled {
+            self.orders.remove(&id);
+        }
+        let levels = self.levels_mut(side);
+        let level = levels.get_mut(&price).expect("Level must exist");
+        level.quantity -= quantity;
+        if filled {
+            level.queue.pop_front();
+            level.orders -= 1;
+            if level.orders == 0 {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    /// The live orders at a level, oldest first.
+    fn live<'a>(&'a self, level: &'a Level) -> impl Iterator<Item = &'a Order<T>> + 'a {
+        level.queue.iter().filter_map(|id| self.orders.get(id))
+    }
+
+    /// The levels of one side of the book.
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Word, Level> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+}
+
+impl PriceLevel {
+    fn new((price, level): (&Word, &Level)) -> Self {
+        Self {
+            price: *price,
+            quantity: level.quantity,
+            orders: level.orders,
+        }
+    }
+}
+
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(id) => write!(f, "Order {} is not in the book", id),
+            Self::NotOwner(id) => write!(f, "Order {} belongs to another owner", id),
+        }
+    }
+}
+
+impl std::error::Error for CancelError {}
+
+/// Serializes as the `bids` and `asks` in priority order.
+impl<T: Serialize> Serialize for OrderBook<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut book = serializer.serialize_struct("OrderBook", 2)?;
+        book.serialize_field("bids", &self.bids().collect::<Vec<_>>())?;
+        book.serialize_field("asks", &self.asks().collect::<Vec<_>>())?;
+        book.end()
+    }
+}
@@ -0,0 +1,262 @@
+use matching_engine::{CancelError, OrderBook, Owner, PriceLevel, Side};
+
+const ALICE: Owner = [1, 1, 1, 1];
+const BOB: Owner = [2, 2, 2, 2];
+
+#[test]
+fn crossing_orders_fill() {
+    let mut book = OrderBook::new();
+    let bid = book.add_bid(ALICE, 100, 10, "bid");
+    let ask = book.add_ask(BOB, 95, 4, "ask");
+    assert_ne!(bid, ask);
+
+    let fills = book.match_orders();
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].bid.id, bid);
+    assert_eq!(fills[0].bid.owner, ALICE);
+    assert_eq!(fills[0].bid.data, "bid");
+    assert_eq!(fills[0].ask.id, ask);
+    assert_eq!(fills[0].ask.owner, BOB);
+    assert_eq!(fills[0].quantity, 4);
+
+    // The bid is left with the remaining quantity
+    assert_eq!(book.asks().count(), 0);
+    assert_eq!(book.bids().count(), 1);
+    assert_eq!(book.get(bid).unwrap().quantity, 6);
+}
+
+#[test]
+fn best_prices_fill_first() {
+    let mut book = OrderBook::new();
+    book.add_bid(ALICE, 90, 5, ());
+    let best_bid = book.add_bid(ALICE, 110, 5, ());
+    book.add_ask(BOB, 105, 5, ());
+    let best_ask = book.add_ask(BOB, 100, 5, ());
+
+    let fills = book.match_orders();
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].bid.id, best_bid);
+    assert_eq!(fills[0].ask.id, best_ask);
+
+    // The rest of the book no longer crosses
+    assert_eq!(book.best_bid(), Some(90));
+    assert_eq!(book.best_ask(), Some(105));
+    assert!(book.match_orders().is_empty());
+}
+
+#[test]
+fn one_bid_fills_many_asks() {
+    let mut book = OrderBook::new();
+    book.add_bid(ALICE, 100, 10, ());
+    book.add_ask(BOB, 98, 3, ());
+    book.add_ask(BOB, 99, 3, ());
+    book.add_ask(BOB, 100, 3, ());
+    book.add_ask(BOB, 101, 3, ());
+
+    let fills = book.match_orders();
+    let quantities: Vec<_> = fills.iter().map(|f| f.quantity).collect();
+    assert_eq!(quantities, [3, 3, 3]);
+    assert_eq!(book.bids().next().unwrap().quantity, 1);
+    assert_eq!(book.asks().count(), 1);
+    assert_eq!(book.best_ask(), Some(101));
+}
+
+#[test]
+fn sequences_increase() {
+    let mut book = OrderBook::new();
+    book.add_bid(ALICE, 100, 1, ());
+    book.add_ask(BOB, 200, 1, ());
+    book.add_bid(ALICE, 90, 1, ());
+    book.add_ask(BOB, 210, 1, ());
+    let mut sequences: Vec<_> = book.bids().chain(book.asks()).map(|o| o.sequence).collect();
+    sequences.sort();
+    sequences.dedup();
+    assert_eq!(sequences, [0, 1, 2, 3]);
+}
+
+#[test]
+fn older_bids_at_the_same_price_fill_first() {
+    let mut book = OrderBook::new();
+    let first = book.add_bid(ALICE, 100, 5, ());
+    book.add_bid(BOB, 99, 5, ());
+    let second = book.add_bid(BOB, 100, 5, ());
+    let third = book.add_bid(ALICE, 100, 5, ());
+    book.add_ask(BOB, 100, 12, ());
+
+    let fills = book.match_orders();
+    let bids: Vec<_> = fills.iter().map(|f| (f.bid.id, f.quantity)).collect();
+    assert_eq!(bids, [(first, 5), (second, 5), (third, 2)]);
+
+    // The partially filled bid keeps its place ahead of later bids
+    let later = book.add_bid(BOB, 100, 5, ());
+    let ids: Vec<_> = book.bids().map(|b| b.id).collect();
+    assert_eq!(ids[..2], [third, later]);
+    assert_eq!(book.get(third).unwrap().quantity, 3);
+}
+
+#[test]
+fn older_asks_at_the_same_price_fill_first() {
+    let mut book = OrderBook::new();
+    let first = book.add_ask(ALICE, 100, 5, ());
+    book.add_ask(BOB, 101, 5, ());
+    let second = book.add_ask(BOB, 100, 5, ());
+    book.add_bid(ALICE, 100, 8, ());
+
+    let fills = book.match_orders();
+    let asks: Vec<_> = fills.iter().map(|f| (f.ask.id, f.quantity)).collect();
+    assert_eq!(asks, [(first, 5), (second, 3)]);
+    let ask = book.asks().next().unwrap();
+    assert_eq!(ask.id, second);
+    assert_eq!(ask.quantity, 2);
+}
+
+#[test]
+fn cancelled_orders_never_fill() {
+    let mut book = OrderBook::new();
+    let first = book.add_ask(ALICE, 100, 5, ());
+    let second = book.add_ask(BOB, 100, 5, ());
+    let third = book.add_ask(ALICE, 100, 5, ());
+    let other = book.add_ask(BOB, 101, 5, ());
+
+    assert_eq!(book.cancel(first).unwrap().owner, ALICE);
+    assert_eq!(book.cancel(first), None);
+    assert_eq!(book.cancel(other).unwrap().price, 101);
+    assert_eq!(book.len(), 2);
+    assert_eq!(book.best_ask(), Some(100));
+
+    book.add_bid(BOB, 101, 8, ());
+    let fills = book.match_orders();
+    let asks: Vec<_> = fills.iter().map(|f| (f.ask.id, f.quantity)).collect();
+    assert_eq!(asks, [(second, 5), (third, 3)]);
+
+    // Cancelling the last order at a price removes the level
+    book.cancel(third).unwrap();
+    assert_eq!(book.best_ask(), None);
+    assert!(book.is_empty());
+}
+
+#[test]
+fn only_the_owner_can_cancel() {
+    let mut book = OrderBook::new();
+    let bid = book.add_bid(ALICE, 100, 5, ());
+    let other = book.add_bid(BOB, 100, 5, ());
+
+    assert_eq!(book.cancel_owned(bid, BOB), Err(CancelError::NotOwner(bid)));
+    assert!(book.get(bid).is_some());
+    assert_eq!(book.cancel_owned(bid, ALICE).unwrap().id, bid);
+    assert_eq!(
+        book.cancel_owned(bid, ALICE),
+        Err(CancelError::NotFound(bid))
+    );
+
+    // Other orders at the same price are untouched
+    assert_eq!(book.bids().map(|b| b.id).collect::<Vec<_>>(), [other]);
+}
+
+#[test]
+fn orders_crossing_their_owners_orders_are_found() {
+    let mut book = OrderBook::new();
+    book.add_ask(ALICE, 100, 5, ());
+    let cancelled = book.add_ask(ALICE, 95, 5, ());
+    book.add_ask(BOB, 90, 5, ());
+    book.add_bid(ALICE, 80, 5, ());
+    book.cancel(cancelled);
+
+    // Alice's bid at 100 would fill her own ask, but at 99 only Bob's
+    assert!(book.crosses_own(Side::Bid, ALICE, 100));
+    assert!(!book.crosses_own(Side::Bid, ALICE, 99));
+    assert!(book.crosses_own(Side::Bid, BOB, 90));
+    assert!(!book.crosses_own(Side::Bid, BOB, 89));
+    // Alice's ask at 80 would fill her own bid
+    assert!(book.crosses_own(Side::Ask, ALICE, 80));
+    assert!(!book.crosses_own(Side::Ask, ALICE, 81));
+    assert!(!book.crosses_own(Side::Ask, BOB, 70));
+}
+
+#[test]
+fn settled_fills_reduce_orders() {
+    let mut book = OrderBook::new();
+    let bid = book.add_bid(ALICE, 100, 10, ());
+    let ask = book.add_ask(BOB, 100, 4, ());
+
+    // Fills are matched on a copy and applied once settled
+    let fills = book.clone().match_orders();
+    assert_eq!(book.len(), 2);
+    for fill in &fills {
+        assert_eq!(
+            book.reduce(fill.bid.id, fill.quantity).unwrap().quantity,
+            10
+        );
+        assert_eq!(book.reduce(fill.ask.id, fill.quantity).unwrap().quantity, 4);
+    }
+    assert_eq!(book.get(bid).unwrap().quantity, 6);
+    assert!(book.get(ask).is_none());
+    assert_eq!(book.best_ask(), None);
+    assert!(book.reduce(ask, 1).is_none());
+
+    // Reducing an order that is not at the front keeps the queue intact
+    let first = book.add_bid(BOB, 100, 2, ());
+    book.reduce(first, 2).unwrap();
+    assert_eq!(book.bids().map(|b| b.id).collect::<Vec<_>>(), [bid]);
+    book.add_ask(BOB, 100, 6, ());
+    assert_eq!(book.match_orders()[0].bid.id, bid);
+    assert!(book.is_empty());
+}
+
+#[test]
+fn restored_books_keep_priority_and_ids() {
+    let mut book = OrderBook::new();
+    let first = book.add_ask(ALICE, 100, 5, ());
+    let cancelled = book.add_ask(BOB, 100, 5, ());
+    let second = book.add_ask(BOB, 100, 5, ());
+    book.add_bid(ALICE, 90, 5, ());
+    book.cancel(cancelled).unwrap();
+
+    // Orders come back from storage in any order
+    let mut orders: Vec<_> = book.asks().chain(book.bids()).cloned().collect();
+    orders.reverse();
+    let mut restored = OrderBook::restore(orders, book.next_id(), book.next_sequence());
+    assert_eq!(restored.len(), 3);
+    assert_eq!(restored.best_bid(), Some(90));
+    assert_eq!(
+        restored.asks().map(|a| a.id).collect::<Vec<_>>(),
+        [first, second]
+    );
+
+    // New orders never reuse the IDs of removed orders
+    let id = restored.add_ask(BOB, 100, 5, ());
+    assert_eq!(id, book.next_id());
+    assert!(restored.get(id).unwrap().sequence > restored.get(second).unwrap().sequence);
+    restored.add_bid(ALICE, 100, 7, ());
+    let fills = restored.match_orders();
+    let asks: Vec<_> = fills.iter().map(|f| (f.ask.id, f.quantity)).collect();
+    assert_eq!(asks, [(first, 5), (second, 2)]);
+}
+
+#[test]
+fn levels_aggregate_live_orders() {
+    let mut book = OrderBook::new();
+    book.add_bid(ALICE, 90, 5, ());
+    book.add_bid(ALICE, 100, 3, ());
+    let cancelled = book.add_bid(BOB, 100, 4, ());
+    book.add_bid(BOB, 100, 2, ());
+    book.add_ask(BOB, 110, 1, ());
+    book.add_ask(BOB, 105, 6, ());
+    book.cancel(cancelled).unwrap();
+
+    let level = |price, quantity, orders| PriceLevel {
+        price,
+        quantity,
+        orders,
+    };
+    assert_eq!(
+        book.bid_levels().collect::<Vec<_>>(),
+        [level(100, 5, 2), level(90, 5, 1)]
+    );
+    assert_eq!(
+        book.ask_levels().collect::<Vec<_>>(),
+        [level(105, 6, 1), level(110, 1, 1)]
+    );
+    assert_eq!(book.level(Side::Bid, 100), Some(level(100, 5, 2)));
+    assert_eq!(book.level(Side::Ask, 100), None);
+}
@@ -0,0 +1,119 @@
+//! # Auth
+//! Checks that orders and cancels are signed by the order owner.
+//!
+//! This is the same check as `@verify_key`: the key recovered from the
+//! signature over the sha256 of the words must hash to the owner.
+
+use anyhow::Context;
+use essential_sign::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use essential_types::{ContentAddress, Signature, Word};
+use order_app::{
+    book::{self, CancelOrder, NewOrder, Side},
+    deploy::Pair,
+};
+
+/// Checks the owner signed the order for the side in the pair's market.
+pub fn verify_order(pair: &Pair, side: Side, order: &NewOrder) -> anyhow::Result<()> {
+    verify(&order.to_words(side, pair), &order.signature, order.owner)
+}
+
+/// Checks the owner signed the cancel of the order with the ID and contract in the pair's market.
+pub fn verify_cancel(
+    pair: &Pair,
+    id: u64,
+    contract: &ContentAddress,
+    cancel: &CancelOrder,
+) -> anyhow::Result<()> {
+    verify(
+        &book::cancel_words(pair, id, contract),
+        &cancel.signature,
+        cancel.owner,
+    )
+}
+
+/// Checks the owner signed the removal of their orders with the IDs on the side at the price.
+pub fn verify_remove(
+    pair: &Pair,
+    side: Side,
+    price: Word,
+    ids: &[u64],
+    remove: &CancelOrder,
+) -> anyhow::Result<()> {
+    verify(
+        &book::remove_words(pair, side, price, ids),
+        &remove.signature,
+        remove.owner,
+    )
+}
+
+fn verify(words: &[Word], signature: &Signature, owner: [Word; 4]) -> anyhow::Result<()> {
+    let signature = decode(signature).context("Malformed signature")?;
+    token::verify::verify_key(words, &signature, owner)
+}
+
+/// Decodes a signature from its compact form and recovery ID.
+fn decode(signature: &Signature) -> anyhow::Result<RecoverableSignature> {
+    let id = RecoveryId::from_i32(i32::from(signature.1))?;
+    Ok(RecoverableSignature::from_compact(&signature.0, id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use essential_sign::secp256k1::SecretKey;
+    use token::signer::{KeySigner, Signer};
+
+    #[test]
+    fn rejects_forged_orders() {
+        let pair = Pair::new(ContentAddress([1; 32]), ContentAddress([2; 32])).unwrap();
+        let other = Pair::new(ContentAddress([2; 32]), ContentAddress([1; 32])).unwrap();
+        let contract = ContentAddress([3; 32]);
+        let mut owner = KeySigner::new(SecretKey::from_slice(&[7; 32]).unwrap());
+        let mut forger = KeySigner::new(SecretKey::from_slice(&[8; 32]).unwrap());
+        let mut order = NewOrder {
+            owner: owner.hashed_key().unwrap(),
+            price: 100,
+            quantity: 10,
+            addresses: vec![],
+            signature: Signature([0; 64], 0),
+        };
+        let sign = |signer: &mut KeySigner, words: &[Word]| {
+            essential_sign::encode::signature(&signer.sign_words(words).unwrap())
+        };
+
+        order.signature = sign(&mut owner, &order.to_words(Side::Bid, &pair));
+        verify_order(&pair, Side::Bid, &order).unwrap();
+        assert!(verify_order(&pair, Side::Ask, &order).is_err());
+        // An order for one market can't be added to another
+        assert!(verify_order(&other, Side::Bid, &order).is_err());
+
+        // Changing a signed field or signing with another key is rejected
+        order.price = 1;
+        assert!(verify_order(&pair, Side::Bid, &order).is_err());
+        order.price = 100;
+        order.signature = sign(&mut forger, &order.to_words(Side::Bid, &pair));
+        assert!(verify_order(&pair, Side::Bid, &order).is_err());
+
+        let cancel = CancelOrder {
+            owner: order.owner,
+            signature: sign(&mut owner, &book::cancel_words(&pair, 3, &contract)),
+        };
+        verify_cancel(&pair, 3, &contract, &cancel).unwrap();
+        assert!(verify_cancel(&pair, 4, &contract, &cancel).is_err());
+        assert!(verify_cancel(&other, 3, &contract, &cancel).is_err());
+        assert!(verify_cancel(&pair, 3, &ContentAddress([4; 32]), &cancel).is_err());
+
+        let remove = CancelOrder {
+            owner: order.owner,
+            signature: sign(
+                &mut owner,
+                &book::remove_words(&pair, Side::Bid, 100, &[3, 5]),
+            ),
+        };
+        verify_remove(&pair, Side::Bid, 100, &[3, 5], &remove).unwrap();
+        // Once an order is gone the removal no longer matches
+        assert!(verify_remove(&pair, Side::Bid, 100, &[5], &remove).is_err());
+        assert!(verify_remove(&pair, Side::Ask, 100, &[3, 5], &remove).is_err());
+        assert!(verify_remove(&other, Side::Bid, 100, &[3, 5], &remove).is_err());
+    }
+}
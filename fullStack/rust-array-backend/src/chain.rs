@@ -0,0 +1,360 @@
+//! # Chain
+//! Checks orders against the state of their order contract instance.
+//!
+//! The price, quantity and owner a client sends are only claims. The order
+//! contract's `bid_amount`, `price` and `owner` storage is the truth, so the
+//! book only holds orders that agree with it.
+//!
+//! The addresses are only claims too. An order is only added once its
+//! contract is shown to be an order contract instance for the market's pair,
+//! by compiling the instance again from the nonce it was placed with.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use anyhow::{bail, ensure};
+use essential_rest_client::node_client::EssentialNodeClient;
+use essential_types::{ContentAddress, Key, PredicateAddress, Word};
+use matching_engine::{Order, Owner};
+use order_app::{
+    book::Side,
+    deploy::{self, Deployment, Instance, Pair},
+    Query,
+};
+
+use crate::{Addresses, OrderBook};
+
+/// The state of an order contract instance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnChain {
+    /// The amount of token A left to fill.
+    pub amount: Word,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The hashed key of the order owner.
+    pub owner: Owner,
+}
+
+/// How many compiled instances [`Instances`] keeps.
+const MAX_INSTANCES: usize = 10_000;
+
+/// The order contract instances compiled so far, by pair and nonce.
+///
+/// Compiling an instance is slow, so an order added again is checked
+/// against the instance compiled the first time. The oldest are dropped
+/// once there are [`MAX_INSTANCES`].
+#[derive(Default)]
+pub struct Instances(Mutex<CompiledInstances>);
+
+#[derive(Default)]
+struct CompiledInstances {
+    deployments: HashMap<(Pair, Word), Deployment>,
+    order: VecDeque<(Pair, Word)>,
+}
+
+impl Instances {
+    /// The deployment of the instance, if it has been compiled.
+    fn get(&self, pair: &Pair, nonce: Word) -> Option<Deployment> {
+        let compiled = self.0.lock().unwrap();
+        compiled.deployments.get(&(pair.clone(), nonce)).cloned()
+    }
+
+    /// Keeps the deployment of a compiled instance.
+    fn insert(&self, deployment: Deployment) {
+        let mut compiled = self.0.lock().unwrap();
+        let key = (deployment.pair.clone(), deployment.nonce);
+        if compiled
+            .deployments
+            .insert(key.clone(), deployment)
+            .is_none()
+        {
+            compiled.order.push_back(key);
+        }
+        while compiled.order.len() > MAX_INSTANCES {
+            if let Some(oldest) = compiled.order.pop_front() {
+                compiled.deployments.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Returns the order contract instance all the addresses belong to.
+pub fn contract(addresses: &[PredicateAddress]) -> anyhow::Result<&ContentAddress> {
+    let [order, update] = addresses else {
+        bail!(
+            "Expected the order and update predicates, got {} addresses",
+            addresses.len()
+        )
+    };
+    ensure!(
+        order.contract == update.contract,
+        "Order predicates belong to different contracts"
+    );
+    Ok(&order.contract)
+}
+
+/// Checks the addresses are the side's predicate then the `UpdateVars` predicate of the deployment.
+pub fn check_addresses(
+    side: Side,
+    addresses: &[PredicateAddress],
+    deployment: &Deployment,
+) -> anyhow::Result<()> {
+    let (name, predicate) = match side {
+        Side::Bid => ("Bid", &deployment.bid),
+        Side::Ask => ("Ask", &deployment.ask),
+    };
+    let [order, update] = addresses else {
+        bail!(
+            "Expected the order and update predicates, got {} addresses",
+            addresses.len()
+        )
+    };
+    ensure!(
+        order == predicate,
+        "Expected the {} predicate of order {}",
+        name,
+        deployment.contract
+    );
+    ensure!(
+        update == &deployment.update,
+        "Expected the UpdateVars predicate of order {}",
+        deployment.contract
+    );
+    Ok(())
+}
+
+/// Checks the addresses belong to an order contract instance for the pair
+/// and returns its deployment.
+///
+/// The instance is compiled again from the pair and the nonce it was placed
+/// with, so this requires `pint` be available on PATH. Compiled instances are
+/// kept in `instances`.
+pub async fn instance(
+    node: &EssentialNodeClient,
+    instances: &Instances,
+    pair: &Pair,
+    side: Side,
+    addresses: &[PredicateAddress],
+) -> anyhow::Result<Deployment> {
+    let contract = contract(addresses)?;
+    let nonce = state(node, contract, order_app::nonce_key()).await?;
+    ensure!(
+        !order_app::is_nil(&nonce),
+        "Order {} has not been placed",
+        contract
+    );
+    let nonce = order_app::nonce(nonce)?;
+    let deployment = match instances.get(pair, nonce) {
+        Some(deployment) => deployment,
+        None => {
+            let instance = Instance {
+                pair: pair.clone(),
+                nonce,
+            };
+            let deployment = deploy::compile(&instance).await?.deployment;
+            instances.insert(deployment.clone());
+            deployment
+        }
+    };
+    ensure!(
+        &deployment.contract == contract,
+        "Order {} is not an order contract for {}",
+        contract,
+        pair
+    );
+    check_addresses(side, addresses, &deployment)?;
+    Ok(deployment)
+}
+
+/// Returns the state an order claims its contract has.
+///
+/// Fails if the price or quantity is not positive, as a filled or cancelled
+/// order has nothing left to match.
+pub fn claim(owner: Owner, price: Word, quantity: Word) -> anyhow::Result<OnChain> {
+    ensure!(price > 0, "Price must be greater than zero");
+    ensure!(quantity > 0, "Quantity must be greater than zero");
+    Ok(OnChain {
+        amount: quantity,
+        price,
+        owner,
+    })
+}
+
+/// Queries the state of an order contract instance.
+pub async fn query(
+    node: &EssentialNodeClient,
+    contract: &ContentAddress,
+) -> anyhow::Result<OnChain> {
+    let amount = state(node, contract, order_app::bid_amount_key()).await?;
+    let price = state(node, contract, order_app::price_key()).await?;
+    let owner = state(node, contract, order_app::owner_key()).await?;
+    ensure!(
+        !order_app::is_nil(&amount) && !order_app::is_nil(&price),
+        "Order {} has not been placed",
+        contract
+    );
+    let Some(owner) = order_app::owner(owner)? else {
+        bail!("Order {} has no owner", contract)
+    };
+    Ok(OnChain {
+        amount: order_app::bid_amount(amount)?,
+        price: order_app::price(price)?,
+        owner,
+    })
+}
+
+/// Checks an order against the state of its contract.
+pub async fn verify(
+    node: &EssentialNodeClient,
+    addresses: &[PredicateAddress],
+    owner: Owner,
+    price: Word,
+    quantity: Word,
+) -> anyhow::Result<()> {
+    let claimed = claim(owner, price, quantity)?;
+    let on_chain = query(node, contract(addresses)?).await?;
+    ensure!(
+        on_chain == claimed,
+        "Order does not match its contract: claimed {:?}, on chain {:?}",
+        claimed,
+        on_chain
+    );
+    Ok(())
+}
+
+/// Removes the orders that no longer match their contract and returns them.
+///
+/// The book is not locked while the node is queried, so an order that
+/// changed in the meantime is left for the next check.
+pub async fn prune(
+    node: &EssentialNodeClient,
+    order_book: &Mutex<OrderBook>,
+) -> Vec<Order<Addresses>> {
+    let orders: Vec<_> = {
+        let order_book = order_book.lock().unwrap();
+        order_book
+            .bids()
+            .chain(order_book.asks())
+            .map(|order| order.clone())
+            .collect()
+    };
+    let mut stale = Vec::new();
+    for order in orders {
+        let result = verify(
+            node,
+            &order.data.addresses,
+            order.owner,
+            order.price,
+            order.quantity,
+        )
+        .await;
+        if result.is_err() {
+            stale.push(order);
+        }
+    }
+
+    let mut order_book = order_book.lock().unwrap();
+    stale
+        .into_iter()
+        .filter(|order| order_book.get(order.id).map(|o| o.quantity) == Some(order.quantity))
+        .filter_map(|order| order_book.cancel(order.id))
+        .collect()
+}
+
+async fn state(
+    node: &EssentialNodeClient,
+    contract: &ContentAddress,
+    key: Key,
+) -> anyhow::Result<Query> {
+    Ok(Query(node.query_state(contract.clone(), key).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment() -> Deployment {
+        let contract = ContentAddress([1; 32]);
+        let predicate = |byte| PredicateAddress {
+            contract: contract.clone(),
+            predicate: ContentAddress([byte; 32]),
+        };
+        Deployment {
+            bid: predicate(2),
+            ask: predicate(3),
+            update: predicate(4),
+            place: predicate(5),
+            contract,
+            nonce: 7,
+            pair: Pair::new(ContentAddress([8; 32]), ContentAddress([9; 32])).unwrap(),
+        }
+    }
+
+    #[test]
+    fn checks_the_predicate_for_the_side() {
+        let deployment = deployment();
+        let bid = [deployment.bid.clone(), deployment.update.clone()];
+        let ask = [deployment.ask.clone(), deployment.update.clone()];
+        check_addresses(Side::Bid, &bid, &deployment).unwrap();
+        check_addresses(Side::Ask, &ask, &deployment).unwrap();
+
+        // A bid can't claim the Ask predicate, or the other way around
+        assert!(check_addresses(Side::Bid, &ask, &deployment).is_err());
+        assert!(check_addresses(Side::Ask, &bid, &deployment).is_err());
+
+        // The second address must be UpdateVars, not another predicate of the instance
+        let place = [deployment.bid.clone(), deployment.place.clone()];
+        assert!(check_addresses(Side::Bid, &place, &deployment).is_err());
+        assert!(check_addresses(Side::Bid, &bid[..1], &deployment).is_err());
+    }
+
+    #[test]
+    fn rejects_addresses_from_another_contract() {
+        let deployment = deployment();
+        let mut other = deployment.bid.clone();
+        other.contract = ContentAddress([6; 32]);
+        assert!(contract(&[other.clone(), deployment.update.clone()]).is_err());
+        let addresses = [other, deployment.update.clone()];
+        assert!(check_addresses(Side::Bid, &addresses, &deployment).is_err());
+    }
+
+    #[test]
+    fn keeps_the_latest_instances() {
+        let instances = Instances::default();
+        let deployment = deployment();
+        let pair = deployment.pair.clone();
+        assert_eq!(instances.get(&pair, 7), None);
+        instances.insert(deployment.clone());
+        assert_eq!(instances.get(&pair, 7), Some(deployment.clone()));
+        assert_eq!(instances.get(&pair, 8), None);
+
+        for nonce in 8..8 + MAX_INSTANCES as Word {
+            instances.insert(Deployment {
+                nonce,
+                ..deployment.clone()
+            });
+        }
+        assert_eq!(instances.get(&pair, 7), None);
+        assert!(instances.get(&pair, 8).is_some());
+    }
+
+    #[test]
+    fn claims_must_be_positive() {
+        let owner = [1; 4];
+        assert_eq!(
+            claim(owner, 100, 10).unwrap(),
+            OnChain {
+                amount: 10,
+                price: 100,
+                owner
+            }
+        );
+        // A filled or cancelled contract has no amount left, so can't be added with none
+        assert!(claim(owner, 100, 0).is_err());
+        assert!(claim(owner, 0, 10).is_err());
+        assert!(claim(owner, -1, 10).is_err());
+        assert!(claim(owner, 100, -10).is_err());
+    }
+}
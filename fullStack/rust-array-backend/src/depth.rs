@@ -0,0 +1,174 @@
+//! # Depth
+//! Aggregates the book into price levels for display.
+//!
+//! Levels can be grouped into buckets of `group` ticks. Bids round down and
+//! asks round up, so a bucket never shows a better price than its orders.
+
+use anyhow::bail;
+use essential_types::Word;
+use matching_engine::PriceLevel;
+use serde::Serialize;
+
+use crate::OrderBook;
+
+/// The aggregated book.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Depth {
+    /// The highest bid price.
+    pub best_bid: Option<Word>,
+    /// The lowest ask price.
+    pub best_ask: Option<Word>,
+    /// The best ask less the best bid.
+    pub spread: Option<Word>,
+    /// Halfway between the best bid and the best ask.
+    pub mid: Option<f64>,
+    /// Bid levels, best price first.
+    pub bids: Vec<DepthLevel>,
+    /// Ask levels, best price first.
+    pub asks: Vec<DepthLevel>,
+}
+
+/// The orders at one price or bucket of prices.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DepthLevel {
+    /// The price of the level, or the bucket edge nearest the spread.
+    pub price: Word,
+    /// The total quantity at the level.
+    pub quantity: Word,
+    /// The number of orders at the level.
+    pub orders: usize,
+    /// The total quantity at this level and every better one.
+    pub total: Word,
+}
+
+/// Aggregates at most `levels` levels on each side of the book,
+/// grouping prices into buckets of `group` ticks.
+pub fn depth(book: &OrderBook, levels: usize, group: Word) -> anyhow::Result<Depth> {
+    if group <= 0 {
+        bail!("The group must be at least 1 tick, not {}", group);
+    }
+    let (best_bid, best_ask) = (book.best_bid(), book.best_ask());
+    let (spread, mid) = match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => (Some(ask - bid), Some((bid as f64 + ask as f64) / 2.0)),
+        _ => (None, None),
+    };
+    Ok(Depth {
+        best_bid,
+        best_ask,
+        spread,
+        mid,
+        bids: side(book.bid_levels(), levels, |price| {
+            price.div_euclid(group) * group
+        }),
+        asks: side(book.ask_levels(), levels, |price| {
+            -(-price).div_euclid(group) * group
+        }),
+    })
+}
+
+/// Groups the levels of one side, best first, into buckets.
+fn side(
+    levels: impl Iterator<Item = PriceLevel>,
+    max: usize,
+    bucket: impl Fn(Word) -> Word,
+) -> Vec<DepthLevel> {
+    let mut grouped: Vec<DepthLevel> = Vec::new();
+    let mut total = 0;
+    for level in levels {
+        let price = bucket(level.price);
+        total += level.quantity;
+        match grouped.last_mut() {
+            Some(last) if last.price == price => {
+                last.quantity += level.quantity;
+                last.orders += level.orders;
+                last.total = total;
+            }
+            _ if grouped.len() == max => break,
+            _ => grouped.push(DepthLevel {
+                price,
+                quantity: level.quantity,
+                orders: level.orders,
+                total,
+            }),
+        }
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Addresses;
+
+    fn book() -> OrderBook {
+        let mut book = OrderBook::new();
+        for (price, quantity) in [(100, 2), (100, 3), (98, 4), (95, 1), (91, 6)] {
+            book.add_bid([1; 4], price, quantity, Addresses::default());
+        }
+        for (price, quantity) in [(103, 5), (104, 1), (106, 2), (111, 7)] {
+            book.add_ask([2; 4], price, quantity, Addresses::default());
+        }
+        book
+    }
+
+    fn level(price: Word, quantity: Word, orders: usize, total: Word) -> DepthLevel {
+        DepthLevel {
+            price,
+            quantity,
+            orders,
+            total,
+        }
+    }
+
+    #[test]
+    fn aggregates_levels() {
+        let depth = depth(&book(), 3, 1).unwrap();
+        assert_eq!(depth.best_bid, Some(100));
+        assert_eq!(depth.best_ask, Some(103));
+        assert_eq!(depth.spread, Some(3));
+        assert_eq!(depth.mid, Some(101.5));
+        assert_eq!(
+            depth.bids,
+            [level(100, 5, 2, 5), level(98, 4, 1, 9), level(95, 1, 1, 10)]
+        );
+        assert_eq!(
+            depth.asks,
+            [
+                level(103, 5, 1, 5),
+                level(104, 1, 1, 6),
+                level(106, 2, 1, 8)
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_levels_away_from_the_spread() {
+        let depth = depth(&book(), 10, 5).unwrap();
+        assert_eq!(
+            depth.bids,
+            [
+                level(100, 5, 2, 5),
+                level(95, 5, 2, 10),
+                level(90, 6, 1, 16)
+            ]
+        );
+        assert_eq!(
+            depth.asks,
+            [
+                level(105, 6, 2, 6),
+                level(110, 2, 1, 8),
+                level(115, 7, 1, 15)
+            ]
+        );
+        assert!(super::depth(&book(), 10, 0).is_err());
+    }
+
+    #[test]
+    fn one_sided_books_have_no_spread() {
+        let mut book = OrderBook::new();
+        book.add_bid([1; 4], 100, 1, Addresses::default());
+        let depth = depth(&book, 10, 1).unwrap();
+        assert_eq!((depth.spread, depth.mid), (None, None));
+        assert!(depth.asks.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! # Feed
+//! Pushes a market's book and trades to WebSocket clients.
+//!
+//! A client gets a snapshot when it connects, then every change after it.
+//! Each message carries a sequence number one higher than the last, so a
+//! client that sees a gap has missed something and should reconnect to get
+//! a fresh snapshot. Clients that fall too far behind are disconnected.
+//!
+//! Book changes are sent as the new state of a price level, so the level
+//! with a quantity of 0 has been removed.
+
+use std::sync::{Arc, Mutex};
+
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use essential_types::Word;
+use matching_engine::{PriceLevel, Side};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{market::Market, trades::Trade, OrderBook};
+
+/// How many messages a client may fall behind before it is disconnected.
+const CAPACITY: usize = 1024;
+
+/// How many of the latest trades a snapshot carries.
+const SNAPSHOT_TRADES: usize = 50;
+
+/// Sequences and sends the changes of one market.
+pub struct Feed {
+    /// The sequence of the last message.
+    sequence: Mutex<u64>,
+    sender: broadcast::Sender<Event>,
+}
+
+/// A message to clients.
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    /// One higher than the message before.
+    pub sequence: u64,
+    /// What changed.
+    #[serde(flatten)]
+    pub update: Update,
+}
+
+/// What a message holds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Update {
+    /// The whole book and the latest trades, newest first.
+    Snapshot {
+        bids: Vec<PriceLevel>,
+        asks: Vec<PriceLevel>,
+        trades: Vec<Trade>,
+    },
+    /// The new state of a price level.
+    Level {
+        side: Side,
+        price: Word,
+        quantity: Word,
+        orders: usize,
+    },
+    /// A new trade.
+    Trade(Trade),
+}
+
+impl Feed {
+    /// Creates a feed with nothing sent yet.
+    pub fn new() -> Self {
+        Self {
+            sequence: Mutex::new(0),
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    /// Sends the update to every client with the next sequence.
+    ///
+    /// Book updates must be published with the book locked and trades with
+    /// the trades locked, so a snapshot never misses or repeats one.
+    pub fn publish(&self, update: Update) {
+        let mut sequence = self.sequence.lock().unwrap();
+        *sequence += 1;
+        // Nobody may be listening
+        let _ = self.sender.send(Event {
+            sequence: *sequence,
+            update,
+        });
+    }
+
+    /// Sends the state of the level at the price.
+    pub fn level(&self, book: &OrderBook, side: Side, price: Word) {
+        let (quantity, orders) = book
+            .level(side, price)
+            .map_or((0, 0), |level| (level.quantity, level.orders));
+        self.publish(Update::Level {
+            side,
+            price,
+            quantity,
+            orders,
+        });
+    }
+
+    /// Subscribes to the updates after a snapshot of the market.
+    fn subscribe(&self, market: &Market) -> (Event, broadcast::Receiver<Event>) {
+        let book = market.book.lock().unwrap();
+        let trades = market.trades.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let snapshot = Event {
+            sequence: *self.sequence.lock().unwrap(),
+            update: Update::Snapshot {
+                bids: book.bid_levels().collect(),
+                asks: book.ask_levels().collect(),
+                trades: trades.latest().take(SNAPSHOT_TRADES).cloned().collect(),
+            },
+        };
+        (snapshot, receiver)
+    }
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends the market's snapshot and then its updates until the client leaves.
+pub async fn serve(market: Arc<Market>, mut session: Session, mut messages: MessageStream) {
+    let (snapshot, mut updates) = market.feed.subscribe(&market);
+    if send(&mut session, &snapshot).await.is_err() {
+        return;
+    }
+    let reason = loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(event) => {
+                    if send(&mut session, &event).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {
+                    break Some(CloseReason {
+                        code: CloseCode::Again,
+                        description: Some("Fell behind, reconnect for a snapshot".into()),
+                    });
+                }
+                Err(RecvError::Closed) => break None,
+            },
+            message = messages.recv() => match message {
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Close(reason))) => break reason,
+                Some(Ok(_)) => (),
+                Some(Err(_)) | None => break None,
+            },
+        }
+    };
+    let _ = session.close(reason).await;
+}
+
+async fn send(session: &mut Session, event: &Event) -> Result<(), actix_ws::Closed> {
+    let text = serde_json::to_string(event).expect("Events always serialize");
+    session.text(text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Addresses;
+
+    #[test]
+    fn levels_are_sent_in_sequence() {
+        let feed = Feed::new();
+        let mut updates = feed.sender.subscribe();
+        let mut book = OrderBook::new();
+        let id = book.add_bid([1; 4], 100, 5, Addresses::default());
+        feed.level(&book, Side::Bid, 100);
+        book.cancel(id);
+        feed.level(&book, Side::Bid, 100);
+
+        let added = updates.try_recv().unwrap();
+        assert_eq!(
+            serde_json::to_value(&added).unwrap(),
+            serde_json::json!({
+                "sequence": 1,
+                "type": "level",
+                "side": "bid",
+                "price": 100,
+                "quantity": 5,
+                "orders": 1,
+            })
+        );
+        let removed = updates.try_recv().unwrap();
+        assert_eq!(removed.sequence, 2);
+        assert!(matches!(
+            removed.update,
+            Update::Level {
+                quantity: 0,
+                orders: 0,
+                ..
+            }
+        ));
+        assert!(updates.try_recv().is_err());
+    }
+}
@@ -1,340 +1,452 @@
+mod auth;
+mod chain;
+mod depth;
+mod feed;
+mod market;
+mod matcher;
+mod settle;
+mod storage;
+mod trades;
+
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer, Responder, get, post, delete};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder, get, post, delete};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
-use std::process::Command;
-use std::fs;
-use std::io::{self, Write};
-
-use anyhow::bail;
-use essential_types::{
-    solution::{Mutation, Solution, SolutionData},
-    PredicateAddress, Value, Word, ContentAddress,
-};
-
-
-#[derive(Serialize, Deserialize, Clone)]
-struct Order {
-    price: i64,
-    quantity: i64,
+use std::time::Duration;
+
+use essential_rest_client::{builder_client::EssentialBuilderClient, node_client::EssentialNodeClient};
+use matching_engine::{CancelError, OrderId};
+use order_app::book::{CancelOrder, NewOrder, Side};
+use essential_types::{PredicateAddress, Word};
+use market::{Market, Markets};
+use order_app::deploy::Pair;
+use settle::{Settlement, Signers};
+use storage::{SledStore, Store};
+use trades::{History, Interval, Trade};
+
+
+/// The order contract instance the book carries with each order.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Addresses {
     addresses: Vec<PredicateAddress>,
+    /// The unique nonce the instance was compiled with.
+    nonce: Word,
 }
 
-#[derive(Serialize, Clone)]
-struct OrderBook {
-    bids: Vec<Order>,
-    asks: Vec<Order>,
-}
+type OrderBook = matching_engine::OrderBook<Addresses>;
 
 
+/// What every market shares.
+struct Services {
+    node: Arc<EssentialNodeClient>,
+    builder: Arc<EssentialBuilderClient>,
+    signers: Arc<Mutex<Signers>>,
+    instances: chain::Instances,
+    store: Arc<dyn Store>,
+    config: matcher::Config,
+}
 
-#[get("/array")]
-async fn get_array(order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let order_book = order_book.lock().unwrap();
-    web::Json(order_book.clone())
+impl Services {
+    /// Creates a market for the pair with the book and trade history and starts matching it.
+    fn open(&self, markets: &Markets, pair: Pair, book: OrderBook, trades: History) -> (Arc<Market>, bool) {
+        let settlement = Settlement {
+            node: self.node.clone(),
+            builder: self.builder.clone(),
+            pair: pair.clone(),
+            signers: self.signers.clone(),
+            timeout: Duration::from_secs(30),
+        };
+        let (market, added) = markets.insert(Market::new(pair, book, trades, settlement, self.store.clone()));
+        if added {
+            actix_web::rt::spawn(matcher::run(self.config.clone(), market.clone(), self.node.clone()));
+        }
+        (market, added)
+    }
 }
 
-#[post("/add_bid")]
-async fn add_bid(order: web::Json<Order>, order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let mut order_book = order_book.lock().unwrap();
-    order_book.bids.push(order.into_inner());
-    order_book.bids.sort_by(|a, b| b.price.cmp(&a.price));
-    "Bid added"
+/// Responds that there is no market with the ID.
+fn no_market(id: &str) -> HttpResponse {
+    HttpResponse::NotFound().body(format!("No market {}", id))
 }
 
-#[post("/add_ask")]
-async fn add_ask(order: web::Json<Order>, order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let mut order_book = order_book.lock().unwrap();
-    order_book.asks.push(order.into_inner());
-    order_book.asks.sort_by(|a, b| a.price.cmp(&b.price));
-    "Ask added"
+#[get("/markets")]
+async fn list_markets(markets: web::Data<Markets>) -> impl Responder {
+    web::Json(markets.summaries())
 }
 
-#[derive(Deserialize)]
-struct PriceQuery {
-    price: i64,
+/// Creates the market for a pair once both tokens are deployed.
+#[post("/markets")]
+async fn create_market(pair: web::Json<Pair>, markets: web::Data<Markets>, services: web::Data<Services>) -> HttpResponse {
+    let Pair { token_a, token_b } = pair.into_inner();
+    let pair = match Pair::new(token_a, token_b) {
+        Ok(pair) => pair,
+        Err(err) => return HttpResponse::BadRequest().body(err.to_string()),
+    };
+    if let Err(err) = pair.check_deployed(&services.node).await {
+        return HttpResponse::UnprocessableEntity().body(err.to_string());
+    }
+    let (store, added) = (services.store.clone(), pair.clone());
+    match web::block(move || store.add_market(&added)).await {
+        Ok(Ok(())) => (),
+        Ok(Err(err)) => return HttpResponse::InternalServerError().body(format!("{:#}", err)),
+        Err(err) => return HttpResponse::InternalServerError().body(err.to_string()),
+    }
+    match services.open(&markets, pair, OrderBook::new(), History::default()) {
+        (market, true) => HttpResponse::Created().json(market.summary()),
+        (market, false) => HttpResponse::Ok().json(market.summary()),
+    }
 }
 
-#[delete("/remove_bid")]
-async fn remove_bid(query: web::Query<PriceQuery>, order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let mut order_book = order_book.lock().unwrap();
-    order_book.bids.retain(|order| order.price != query.price);
-    "Bid removed"
+#[get("/markets/{id}/book")]
+async fn get_book(id: web::Path<String>, markets: web::Data<Markets>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let order_book = market.book.lock().unwrap();
+    HttpResponse::Ok().json(&*order_book)
 }
 
-#[delete("/remove_ask")]
-async fn remove_ask(query: web::Query<PriceQuery>, order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let mut order_book = order_book.lock().unwrap();
-    order_book.asks.retain(|order| order.price != query.price);
-    "Ask removed"
+/// How many levels to aggregate and how many ticks to group them by.
+#[derive(Deserialize)]
+struct DepthQuery {
+    levels: Option<usize>,
+    group: Option<Word>,
 }
 
-fn match_orders(buy_orders: &mut Vec<Order>, ask_orders: &mut Vec<Order>) -> (Vec<Order>, Vec<Order>, Vec<Order>, Vec<Order>) {
-    // Sort buy orders by price in descending order
-    buy_orders.sort_by(|a, b| b.price.cmp(&a.price));
-    
-    // Sort ask orders by price in ascending order
-    ask_orders.sort_by(|a, b| a.price.cmp(&b.price));
-    
-    let mut matched_orders_bids = Vec::new();
-    let mut matched_orders_asks = Vec::new();
-
-    while !buy_orders.is_empty() && !ask_orders.is_empty() {
-        let mut buy_order = buy_orders[0].clone();
-        let mut ask_order = ask_orders[0].clone();
-        
-        // If the highest buy order is greater than or equal to the lowest ask order, match them
-        if buy_order.price >= ask_order.price {
-            let matched_quantity = buy_order.quantity.min(ask_order.quantity);
-            
-            // Reduce the quantities of both buy and ask orders
-            buy_order.quantity -= matched_quantity;
-            ask_order.quantity -= matched_quantity;
-            
-            // Save the matched order
-            matched_orders_bids.push(
-                Order { price: buy_order.price, quantity: matched_quantity, addresses: buy_order.addresses.clone() }
-            );
-            matched_orders_asks.push(
-                Order { price: ask_order.price, quantity: matched_quantity, addresses: ask_order.addresses.clone() }
-            );
-            
-            // Remove buy or ask orders if they are fully filled
-            if buy_order.quantity == 0 {
-                buy_orders.remove(0);
-            } else {
-                buy_orders[0].quantity = buy_order.quantity;
-            }
-            
-            if ask_order.quantity == 0 {
-                ask_orders.remove(0);
-            } else {
-                ask_orders[0].quantity = ask_order.quantity;
-            }
-        } else {
-            // No more profitable trades can be made
-            break;
-        }
+/// The book aggregated into price levels, 20 on each side by default.
+#[get("/markets/{id}/depth")]
+async fn get_depth(id: web::Path<String>, query: web::Query<DepthQuery>, markets: web::Data<Markets>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let order_book = market.book.lock().unwrap();
+    match depth::depth(&order_book, query.levels.unwrap_or(20), query.group.unwrap_or(1)) {
+        Ok(depth) => HttpResponse::Ok().json(depth),
+        Err(err) => HttpResponse::BadRequest().body(err.to_string()),
     }
-    (matched_orders_bids, matched_orders_asks, buy_orders.clone(), ask_orders.clone())
 }
 
-/*
-//EXAMPLE
-const BID_AMOUNT_KEY: Word = 0;
-const PRICE_KEY: Word = 1;
-const OWNER_KEY: Word = 2;
-const NONCE_KEY: Word = 3;
-
-#[derive(Clone)]
-pub struct QueryVars {
-    pub bid_amount: Option<Value>,
-    pub price: Option<Value>,
-    pub owner: Option<Value>,
-    pub nonce: Option<Value>,
+/// The ID given to a new order.
+#[derive(Serialize)]
+struct Added {
+    id: OrderId,
 }
 
-#[derive(Clone)]
-pub struct StateKey(pub Vec<Word>);
-
-pub fn bid_amount_key() -> StateKey {
-    StateKey(vec![BID_AMOUNT_KEY])
+#[post("/markets/{id}/bids")]
+async fn add_bid(id: web::Path<String>, order: web::Json<NewOrder>, markets: web::Data<Markets>, services: web::Data<Services>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    add_order(Side::Bid, order.into_inner(), &market, &services).await
 }
 
-pub fn price_key() -> StateKey {
-    StateKey(vec![PRICE_KEY])
+#[post("/markets/{id}/asks")]
+async fn add_ask(id: web::Path<String>, order: web::Json<NewOrder>, markets: web::Data<Markets>, services: web::Data<Services>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    add_order(Side::Ask, order.into_inner(), &market, &services).await
 }
 
-pub fn owner_key() -> StateKey {
-    StateKey(vec![OWNER_KEY])
+/// Adds an order to the book if the owner signed it and it matches its order contract instance for the market.
+async fn add_order(side: Side, order: NewOrder, market: &Market, services: &Services) -> HttpResponse {
+    if let Err(err) = auth::verify_order(&market.pair, side, &order) {
+        return HttpResponse::Unauthorized().body(err.to_string());
+    }
+    if let Some(conflict) = conflicts(&market.book.lock().unwrap(), side, order.owner, order.price, &order.addresses) {
+        return conflict;
+    }
+    // An order nobody can sign the transfers for could never settle
+    if !services.signers.lock().unwrap().contains(order.owner) {
+        return HttpResponse::UnprocessableEntity().body(format!("No signer for owner {:?}", order.owner));
+    }
+    let node = services.node.as_ref();
+    let deployment = match chain::instance(node, &services.instances, &market.pair, side, &order.addresses).await {
+        Ok(deployment) => deployment,
+        Err(err) => return HttpResponse::UnprocessableEntity().body(format!("{:#}", err)),
+    };
+    if let Err(err) = chain::verify(node, &order.addresses, order.owner, order.price, order.quantity).await {
+        return HttpResponse::UnprocessableEntity().body(err.to_string());
+    }
+    let NewOrder { owner, price, quantity, addresses, signature: _ } = order;
+    let (id, added) = {
+        let mut order_book = market.book.lock().unwrap();
+        // Checked again now the book is locked, as the book may have changed since
+        if let Some(conflict) = conflicts(&order_book, side, owner, price, &addresses) {
+            return conflict;
+        }
+        let id = match side {
+            Side::Bid => order_book.add_bid(owner, price, quantity, Addresses { addresses, nonce: deployment.nonce }),
+            Side::Ask => order_book.add_ask(owner, price, quantity, Addresses { addresses, nonce: deployment.nonce }),
+        };
+        (id, storage::change(&order_book, id))
+    };
+    // An order that can't be saved would be lost on restart, so reject it.
+    // Later changes to the order are queued behind this save, so none are lost.
+    let saved = market.writer.save(added).await;
+    let mut order_book = market.book.lock().unwrap();
+    if let Err(err) = saved {
+        if order_book.cancel(id).is_some() {
+            market.persist(&order_book, id);
+        }
+        return HttpResponse::InternalServerError().body(format!("{:#}", err));
+    }
+    if let Some(order) = order_book.get(id) {
+        market.feed.level(&order_book, order.side, price);
+    }
+    matcher::poke(&order_book, &market.wake);
+    HttpResponse::Ok().json(Added { id })
 }
 
-pub fn nonce_key() -> StateKey {
-    StateKey(vec![NONCE_KEY])
+/// Whether an order of the same order contract instance already rests in the book.
+/// An instance holds one order, so another order for it is a replay.
+fn rests(order_book: &OrderBook, addresses: &[PredicateAddress]) -> bool {
+    let Ok(contract) = chain::contract(addresses) else { return false };
+    order_book.bids().chain(order_book.asks()).any(|order| chain::contract(&order.data.addresses).ok() == Some(contract))
 }
 
-/// Extract the bid amount from the query result.
-pub fn extract_bid_amount(query: QueryVars) -> anyhow::Result<Word> {
-    match query.bid_amount {
-        Some(bid_amount) => match &bid_amount[..] {
-            [] => Ok(0),
-            [bid_amount] => Ok(*bid_amount),
-            _ => bail!("Expected single word, got: {:?}", bid_amount),
-        },
-        None => Ok(0),
+/// Rejects an order that is already in the book or would trade with one of the owner's own orders.
+fn conflicts(order_book: &OrderBook, side: Side, owner: [Word; 4], price: Word, addresses: &[PredicateAddress]) -> Option<HttpResponse> {
+    if rests(order_book, addresses) {
+        return Some(HttpResponse::Conflict().body("The order's contract already has an order in the book"));
     }
-}
-
-/// Extract the price from the query result.
-pub fn extract_price(query: QueryVars) -> anyhow::Result<Word> {
-    match query.price {
-        Some(price) => match &price[..] {
-            [] => Ok(0),
-            [price] => Ok(*price),
-            _ => bail!("Expected single word, got: {:?}", price),
-        },
-        None => Ok(0),
+    let side = match side {
+        Side::Bid => matching_engine::Side::Bid,
+        Side::Ask => matching_engine::Side::Ask,
+    };
+    if order_book.crosses_own(side, owner, price) {
+        return Some(HttpResponse::Conflict().body("The order would trade with one of the owner's own orders"));
     }
+    None
 }
 
-/// Extract the owner from the query result.
-pub fn extract_owner(query: QueryVars) -> anyhow::Result<Word> {
-    match query.owner {
-        Some(owner) => match &owner[..] {
-            [] => Ok(0),
-            [owner] => Ok(*owner),
-            _ => bail!("Expected single word, got: {:?}", owner),
-        },
-        None => Ok(0),
+#[delete("/markets/{id}/orders/{order}")]
+async fn cancel_order(path: web::Path<(String, OrderId)>, cancel: web::Json<CancelOrder>, markets: web::Data<Markets>) -> HttpResponse {
+    let (id, order) = path.into_inner();
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let mut order_book = market.book.lock().unwrap();
+    // The signature covers the order's contract, so look the order up first
+    let contract = match order_book.get(order).map(|order| chain::contract(&order.data.addresses)) {
+        Some(Ok(contract)) => contract.clone(),
+        Some(Err(err)) => return HttpResponse::InternalServerError().body(err.to_string()),
+        None => return HttpResponse::NotFound().body(CancelError::NotFound(order).to_string()),
+    };
+    if let Err(err) = auth::verify_cancel(&market.pair, order, &contract, &cancel) {
+        return HttpResponse::Unauthorized().body(err.to_string());
     }
-}
-
-/// Extract the nonce from the query result.
-pub fn extract_nonce(query: QueryVars) -> anyhow::Result<Word> {
-    match query.nonce {
-        Some(nonce) => match &nonce[..] {
-            [] => Ok(0),
-            [nonce] => Ok(*nonce),
-            _ => bail!("Expected single word, got: {:?}", nonce),
-        },
-        None => Ok(0),
+    match order_book.cancel_owned(order, cancel.owner) {
+        Ok(order) => {
+            market.persist(&order_book, order.id);
+            market.feed.level(&order_book, order.side, order.price);
+            HttpResponse::Ok().json(order)
+        }
+        Err(err @ CancelError::NotFound(_)) => HttpResponse::NotFound().body(err.to_string()),
+        Err(err @ CancelError::NotOwner(_)) => HttpResponse::Forbidden().body(err.to_string()),
     }
 }
 
-/// Create a solution to place a bid.
-pub fn create_bid_solution(
-    predicate: PredicateAddress,
-    amount: Word,
-) -> Solution {
-    Solution {
-        data: vec![SolutionData {
-            predicate_to_solve: predicate,
-            decision_variables: amount,
-            transient_data: Default::default(),
-            state_mutations: vec![
-                Mutation {
-                    key: vec![BID_AMOUNT_KEY],
-                    value: vec![amount],
-                },
-            ],
-        }],
-    }
+#[delete("/markets/{id}/bids/{price}")]
+async fn remove_bids(path: web::Path<(String, Word)>, remove: web::Json<CancelOrder>, markets: web::Data<Markets>) -> HttpResponse {
+    let (id, price) = path.into_inner();
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    remove_level(Side::Bid, price, &remove, &market)
 }
 
-/// Create a solution to place an ask.
-pub fn create_ask_solution(
-    predicate: PredicateAddress,
-    amount: Word,
-) -> Solution {
-    Solution {
-        data: vec![SolutionData {
-            predicate_to_solve: predicate,
-            decision_variables: Default::default(),
-            transient_data: Default::default(),
-            state_mutations: vec![
-                Mutation {
-                    key: vec![BID_AMOUNT_KEY],
-                    value: vec![amount],
-                },
-                Mutation {
-                    key: vec![PRICE_KEY],
-                    value: vec![0],
-                },
-            ],
-        }],
-    }
+#[delete("/markets/{id}/asks/{price}")]
+async fn remove_asks(path: web::Path<(String, Word)>, remove: web::Json<CancelOrder>, markets: web::Data<Markets>) -> HttpResponse {
+    let (id, price) = path.into_inner();
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    remove_level(Side::Ask, price, &remove, &market)
 }
 
-/// Create a solution to update the bid amount and price.
-pub fn create_update_solution(
-    predicate: PredicateAddress,
-    new_bid_amount: Word,
-    new_price: Word,
-) -> Solution {
-    Solution {
-        data: vec![SolutionData {
-            predicate_to_solve: predicate,
-            decision_variables: Default::default(),
-            transient_data: Default::default(),
-            state_mutations: vec![
-                Mutation {
-                    key: vec![BID_AMOUNT_KEY],
-                    value: vec![new_bid_amount],
-                },
-                Mutation {
-                    key: vec![PRICE_KEY],
-                    value: vec![new_price],
-                },
-            ],
-        }],
+/// Cancels all of the owner's orders on the side at the price if the owner signed the removal.
+/// Other owners' orders at the price are left in the book.
+fn remove_level(side: Side, price: Word, remove: &CancelOrder, market: &Market) -> HttpResponse {
+    let mut order_book = market.book.lock().unwrap();
+    let mut ids: Vec<OrderId> = match side {
+        Side::Bid => order_book.bids().filter(|order| order.price == price && order.owner == remove.owner).map(|order| order.id).collect(),
+        Side::Ask => order_book.asks().filter(|order| order.price == price && order.owner == remove.owner).map(|order| order.id).collect(),
+    };
+    ids.sort_unstable();
+    if let Err(err) = auth::verify_remove(&market.pair, side, price, &ids, remove) {
+        return HttpResponse::Unauthorized().body(err.to_string());
+    }
+    let removed: Vec<_> = ids.into_iter().filter_map(|id| order_book.cancel(id)).collect();
+    for order in &removed {
+        market.persist(&order_book, order.id);
+    }
+    if let Some(order) = removed.first() {
+        market.feed.level(&order_book, order.side, price);
     }
+    HttpResponse::Ok().json(removed)
 }
-*/
-
-#[post("/update")]
-async fn match_update(order_book: web::Data<Arc<Mutex<OrderBook>>>) -> impl Responder {
-    let mut order_book = order_book.lock().unwrap();
-    let mut buy_orders = order_book.bids.clone();
-    let mut ask_orders = order_book.asks.clone();
-    let (matched_orders_bids, matched_orders_asks, new_buys, new_asks) = match_orders(&mut buy_orders, &mut ask_orders);
-
-    /*
-    let a = 0; //"amount"
-    let b = 0; //"amount * price"
-
-    
-    for matched_order in matched_orders_bids {
-        let amount = extract_bid_amount(matched_order.addresses[0].query_vars.clone()).unwrap();
-        let price = extract_price(matched_order.addresses[0].query_vars.clone()).unwrap();
-
-        let new_amount = amount - matched_order.quantity;
-        
-        a = a + matched_order.quantity;
-        b = b - matched_order.quantity * price;
-
-        //do token transfer
-
-        let new_solution = create_bid_solution(matched_order.addresses[0].clone(), new_amount);
-
-        //create and send solution
 
+#[post("/markets/{id}/match")]
+async fn match_market(id: web::Path<String>, markets: web::Data<Markets>, services: web::Data<Services>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let matched = matcher::round(&market, &services.node, services.config.max_fills).await;
+    match matched.error {
+        None => HttpResponse::Ok().json(matched),
+        Some(_) => HttpResponse::BadGateway().json(matched),
     }
+}
 
-    for matched_order in matched_orders_asks {
+#[get("/markets/{id}/metrics")]
+async fn get_metrics(id: web::Path<String>, markets: web::Data<Markets>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let metrics = market.metrics.lock().unwrap();
+    HttpResponse::Ok().json(&*metrics)
+}
 
-    }
-    */
+/// Streams the book and trades over a WebSocket, see [`feed`].
+#[get("/markets/{id}/feed")]
+async fn get_feed(id: web::Path<String>, req: HttpRequest, body: web::Payload, markets: web::Data<Markets>) -> actix_web::Result<HttpResponse> {
+    let Some(market) = markets.get(&id) else { return Ok(no_market(&id)) };
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+    actix_web::rt::spawn(feed::serve(market, session, messages));
+    Ok(response)
+}
 
-    order_book.bids = buy_orders;
-    order_book.asks = ask_orders;
+/// How many trades to return.
+#[derive(Deserialize)]
+struct TradesQuery {
+    limit: Option<usize>,
+}
 
-    order_book.bids.sort_by(|a, b| b.price.cmp(&a.price));
+/// The most recent trades, newest first, 100 by default.
+#[get("/markets/{id}/trades")]
+async fn get_trades(id: web::Path<String>, query: web::Query<TradesQuery>, markets: web::Data<Markets>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let trades = market.trades.lock().unwrap();
+    let recent: Vec<&Trade> = trades.latest().take(query.limit.unwrap_or(100)).collect();
+    HttpResponse::Ok().json(recent)
+}
 
-    order_book.asks.sort_by(|a, b| a.price.cmp(&b.price));
+/// The width of the candles.
+#[derive(Deserialize)]
+struct CandlesQuery {
+    interval: Option<Interval>,
+}
 
+/// The latest OHLCV candles, oldest first, one minute wide by default.
+#[get("/markets/{id}/candles")]
+async fn get_candles(id: web::Path<String>, query: web::Query<CandlesQuery>, markets: web::Data<Markets>) -> HttpResponse {
+    let Some(market) = markets.get(&id) else { return no_market(&id) };
+    let trades = market.trades.lock().unwrap();
+    HttpResponse::Ok().json(trades.candles(query.interval.unwrap_or(Interval::Minute)))
+}
 
-    "updated"
+/// Sets up what every market shares from the environment.
+///
+/// `NODE_API` is the node orders are checked against, `BUILDER_API` is the
+/// builder the solutions are sent to, `SIGNERS_DIR` holds the signing
+/// service sockets of the order owners and `STORE_PATH` is where the books
+/// are saved, `order-book.db` by default.
+fn services() -> anyhow::Result<Services> {
+    let var = |name: &str| std::env::var(name).map_err(|_| anyhow::anyhow!("Set {}", name));
+    Ok(Services {
+        node: Arc::new(EssentialNodeClient::new(var("NODE_API")?)?),
+        builder: Arc::new(EssentialBuilderClient::new(var("BUILDER_API")?)?),
+        signers: Arc::new(Mutex::new(Signers::open(var("SIGNERS_DIR")?.as_ref())?)),
+        instances: chain::Instances::default(),
+        store: Arc::new(SledStore::open(var("STORE_PATH").unwrap_or_else(|_| "order-book.db".into()).as_ref())?),
+        config: matcher::Config::from_env()?,
+    })
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let order_book = Arc::new(Mutex::new(OrderBook {
-        bids: vec![],
-        asks: vec![],
-    }));
+    let services = web::Data::new(services().expect("Failed to set up the backend"));
+    let markets = web::Data::new(Markets::default());
+
+    // Restore the markets saved before the last shutdown
+    for saved in services.store.load().expect("Failed to load the store") {
+        let (pair, book, trades) = storage::restore(saved);
+        services.open(&markets, pair, book, trades);
+    }
 
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(order_book.clone()))
+            .app_data(services.clone())
+            .app_data(markets.clone())
             .wrap(Cors::permissive())
-            .service(get_array)
+            .service(list_markets)
+            .service(create_market)
+            .service(get_book)
+            .service(get_depth)
             .service(add_bid)
             .service(add_ask)
-            .service(remove_bid)
-            .service(remove_ask)
-            .service(match_update)
+            .service(cancel_order)
+            .service(remove_bids)
+            .service(remove_asks)
+            .service(match_market)
+            .service(get_metrics)
+            .service(get_trades)
+            .service(get_candles)
+            .service(get_feed)
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use essential_sign::secp256k1::SecretKey;
+    use essential_types::{ContentAddress, Signature};
+    use token::signer::{KeySigner, Signer};
+
+    #[actix_web::test]
+    async fn rejects_replayed_orders() {
+        let (services, market) = market();
+        let mut owner = KeySigner::new(SecretKey::from_slice(&[7; 32]).unwrap());
+        let order = signed(&mut owner, &market.pair, Side::Bid, vec![predicate(3, 4), predicate(3, 5)]);
+
+        // The signed order already rests in the book, so adding it again is a replay
+        let addresses = Addresses { addresses: order.addresses.clone(), nonce: 0 };
+        market.book.lock().unwrap().add_bid(order.owner, order.price, order.quantity, addresses);
+        let response = add_order(Side::Bid, order, &market, &services).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(market.book.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn rejects_orders_crossing_the_owners_orders() {
+        let (services, market) = market();
+        let mut owner = KeySigner::new(SecretKey::from_slice(&[7; 32]).unwrap());
+        let ask = Addresses { addresses: vec![predicate(6, 4), predicate(6, 5)], nonce: 0 };
+        market.book.lock().unwrap().add_ask(owner.hashed_key().unwrap(), 100, 10, ask);
+
+        // A bid at the owner's ask price would fill it
+        let order = signed(&mut owner, &market.pair, Side::Bid, vec![predicate(3, 4), predicate(3, 5)]);
+        let response = add_order(Side::Bid, order, &market, &services).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(market.book.lock().unwrap().len(), 1);
+    }
+
+    // Helper function to open a market with services that reach no node
+    fn market() -> (Services, Arc<Market>) {
+        let services = Services {
+            node: Arc::new(EssentialNodeClient::new("http://127.0.0.1:1".into()).unwrap()),
+            builder: Arc::new(EssentialBuilderClient::new("http://127.0.0.1:1".into()).unwrap()),
+            signers: Arc::new(Mutex::new(Signers::default())),
+            instances: chain::Instances::default(),
+            store: Arc::new(storage::MemoryStore::default()),
+            config: matcher::Config::from_vars(|_| None).unwrap(),
+        };
+        let pair = Pair::new(ContentAddress([1; 32]), ContentAddress([2; 32])).unwrap();
+        let (market, _) = services.open(&Markets::default(), pair, OrderBook::new(), History::default());
+        (services, market)
+    }
+
+    // Helper function to make the address of a predicate of a contract
+    fn predicate(contract: u8, predicate: u8) -> PredicateAddress {
+        PredicateAddress { contract: ContentAddress([contract; 32]), predicate: ContentAddress([predicate; 32]) }
+    }
+
+    // Helper function to sign an order of 10 at 100 for the owner
+    fn signed(owner: &mut KeySigner, pair: &Pair, side: Side, addresses: Vec<PredicateAddress>) -> NewOrder {
+        let mut order = NewOrder {
+            owner: owner.hashed_key().unwrap(),
+            price: 100,
+            quantity: 10,
+            addresses,
+            signature: Signature([0; 64], 0),
+        };
+        order.signature = essential_sign::encode::signature(&owner.sign_words(&order.to_words(side, pair)).unwrap());
+        order
+    }
+}
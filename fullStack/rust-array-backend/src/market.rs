@@ -0,0 +1,156 @@
+//! # Market
+//! Holds a book for each trading pair.
+//!
+//! A market is identified by its [`Pair`], written as the hex of token A and
+//! token B joined by `-`. Each market has its own book, settlement and
+//! background matcher.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use essential_types::Word;
+use matching_engine::OrderId;
+use order_app::deploy::Pair;
+use serde::Serialize;
+use tokio::sync::{Mutex as AsyncMutex, Notify};
+
+use crate::{
+    feed::{Feed, Update},
+    matcher::{Metrics, Settled},
+    settle::Settlement,
+    storage::{self, Change, Store, Writer},
+    trades::{History, Trade},
+    OrderBook,
+};
+
+/// The book of a trading pair and everything needed to match it.
+pub struct Market {
+    /// The tokens the market trades.
+    pub pair: Pair,
+    /// The resting orders.
+    pub book: Mutex<OrderBook>,
+    /// Settles the fills of this market, one round at a time.
+    pub settlement: AsyncMutex<Settlement>,
+    /// What the matcher has done for this market.
+    pub metrics: Mutex<Metrics>,
+    /// Wakes the background matcher.
+    pub wake: Notify,
+    /// The latest trades and the candles of every trade.
+    pub trades: Mutex<History>,
+    /// Persists the book and trades in the order they change.
+    pub writer: Writer,
+    /// Pushes changes to the book and trades to WebSocket clients.
+    pub feed: Feed,
+}
+
+/// Every market the backend serves.
+#[derive(Default)]
+pub struct Markets(RwLock<BTreeMap<Pair, Arc<Market>>>);
+
+/// A summary of a market.
+#[derive(Serialize)]
+pub struct Summary {
+    /// The market ID.
+    pub id: String,
+    /// The tokens the market trades.
+    pub pair: Pair,
+    /// The number of resting orders.
+    pub orders: usize,
+    /// The highest bid price.
+    pub best_bid: Option<Word>,
+    /// The lowest ask price.
+    pub best_ask: Option<Word>,
+}
+
+impl Market {
+    /// Creates a market with the book and trade history, which may be restored from the store.
+    ///
+    /// Must be called from within the actix runtime, as it starts the market's write task.
+    pub fn new(
+        pair: Pair,
+        book: OrderBook,
+        trades: History,
+        settlement: Settlement,
+        store: Arc<dyn Store>,
+    ) -> Self {
+        Self {
+            writer: Writer::spawn(store, pair.clone()),
+            pair,
+            book: Mutex::new(book),
+            trades: Mutex::new(trades),
+            settlement: AsyncMutex::new(settlement),
+            metrics: Mutex::new(Metrics::default()),
+            wake: Notify::new(),
+            feed: Feed::new(),
+        }
+    }
+
+    /// Queues saving the order with the ID as it is in the book.
+    /// A failure is logged, as the book has already changed.
+    pub fn persist(&self, book: &OrderBook, id: OrderId) {
+        self.writer.send(storage::change(book, id));
+    }
+
+    /// Records a settled fill as the market's next trade and publishes it.
+    ///
+    /// The trade is saved together with the candle buckets it changed.
+    pub fn record(&self, settled: &Settled) {
+        let mut trades = self.trades.lock().unwrap();
+        let trade = Trade::new(trades.next_id(), settled);
+        trades.push(trade.clone());
+        let mut changes = vec![Change::Trade(trade.clone())];
+        changes.extend(
+            trades
+                .buckets(&trade)
+                .into_iter()
+                .map(|(interval, bucket)| Change::Candle(interval, bucket)),
+        );
+        self.writer.send_all(changes);
+        self.feed.publish(Update::Trade(trade));
+    }
+
+    /// Summarizes the market.
+    pub fn summary(&self) -> Summary {
+        let book = self.book.lock().unwrap();
+        Summary {
+            id: self.pair.to_string(),
+            pair: self.pair.clone(),
+            orders: book.len(),
+            best_bid: book.best_bid(),
+            best_ask: book.best_ask(),
+        }
+    }
+}
+
+impl Markets {
+    /// The market with the ID, if it exists.
+    pub fn get(&self, id: &str) -> Option<Arc<Market>> {
+        let pair: Pair = id.parse().ok()?;
+        self.0.read().unwrap().get(&pair).cloned()
+    }
+
+    /// Adds the market unless one already trades its pair.
+    ///
+    /// Returns the market for the pair and whether it was added.
+    pub fn insert(&self, market: Market) -> (Arc<Market>, bool) {
+        let mut markets = self.0.write().unwrap();
+        if let Some(existing) = markets.get(&market.pair) {
+            return (existing.clone(), false);
+        }
+        let market = Arc::new(market);
+        markets.insert(market.pair.clone(), market.clone());
+        (market, true)
+    }
+
+    /// Summaries of every market, ordered by pair.
+    pub fn summaries(&self) -> Vec<Summary> {
+        self.0
+            .read()
+            .unwrap()
+            .values()
+            .map(|market| market.summary())
+            .collect()
+    }
+}
@@ -0,0 +1,469 @@
+//! # Matcher
+//! Matches and settles the book of a market, on request or in the background.
+//!
+//! Each market has its own background loop. It wakes when an order makes the book cross and on a
+//! fixed interval. After waking it waits until no order has come for the
+//! debounce period, so a burst of orders is matched in one round.
+//!
+//! After a failed round the loop backs off, doubling the wait each time up to
+//! a limit. A fill that fails to settle round after round has both of its
+//! orders evicted from the book, so it no longer holds up the fills behind it.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::ensure;
+use essential_rest_client::node_client::EssentialNodeClient;
+use essential_types::{ContentAddress, Word};
+use matching_engine::{Fill, Order, OrderId};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::{chain, market::Market, settle, Addresses, OrderBook};
+
+/// How the background loop runs.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The longest the book goes without a round.
+    pub interval: Duration,
+    /// How long no order must come after waking before matching.
+    pub debounce: Duration,
+    /// The most fills settled in one round.
+    pub max_fills: usize,
+    /// The longest wait after failed rounds.
+    pub max_backoff: Duration,
+    /// How many rounds in a row a fill can fail before its orders are evicted.
+    pub max_attempts: u32,
+}
+
+/// A fill that was settled on chain.
+#[derive(Serialize)]
+pub struct Settled {
+    /// The price both orders traded at.
+    pub price: Word,
+    /// The amount of token A that was filled.
+    pub quantity: Word,
+    /// The bid as it was before the fill.
+    pub bid: Order<Addresses>,
+    /// The ask as it was before the fill.
+    pub ask: Order<Addresses>,
+    /// The address of the settlement solution.
+    pub solution: ContentAddress,
+}
+
+/// The fills a round settled.
+/// If a fill failed to settle, it and every fill after it are left in the book.
+#[derive(Serialize)]
+pub struct Matched {
+    /// The settled fills in the order they were matched.
+    pub fills: Vec<Settled>,
+    /// Why settlement stopped early.
+    pub error: Option<String>,
+    /// The IDs of the bid and ask of the fill that failed to settle.
+    pub failed: Option<(OrderId, OrderId)>,
+}
+
+/// What the matcher has done since the backend started.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Metrics {
+    /// The number of rounds run.
+    pub rounds: u64,
+    /// The number of fills settled.
+    pub fills: u64,
+    /// The number of rounds that stopped on a failed settlement.
+    pub failures: u64,
+    /// The number of orders pruned for no longer matching their contract.
+    pub pruned: u64,
+    /// The number of orders evicted for failing to settle too many times.
+    pub evicted: u64,
+    /// The last round, if any has run.
+    pub last: Option<Round>,
+}
+
+/// A single round of matching.
+#[derive(Clone, Debug, Serialize)]
+pub struct Round {
+    /// How long the round took in milliseconds.
+    pub millis: u128,
+    /// The number of fills the book matched.
+    pub matched: usize,
+    /// The number of fills settled.
+    pub settled: usize,
+    /// Why settlement stopped early.
+    pub error: Option<String>,
+}
+
+/// Tracks failed rounds to back off and evict fills that never settle.
+#[derive(Debug, Default)]
+struct Retry {
+    /// The number of rounds in a row that failed.
+    failures: u32,
+    /// The fill that failed last and how many rounds in a row it failed.
+    stuck: Option<((OrderId, OrderId), u32)>,
+}
+
+impl Config {
+    /// Reads the config from `MATCH_INTERVAL_MS`, `MATCH_DEBOUNCE_MS`,
+    /// `MATCH_MAX_FILLS`, `MATCH_MAX_BACKOFF_MS` and `MATCH_MAX_ATTEMPTS`,
+    /// falling back to defaults for unset variables.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the config from the variables `var` returns.
+    ///
+    /// Fails if the interval, max fills or max attempts is zero, as the loop
+    /// would never wait or never make progress.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let var = |name: &str, default: u64| -> anyhow::Result<u64> {
+            match var(name) {
+                Some(value) => Ok(value.parse()?),
+                None => Ok(default),
+            }
+        };
+        let config = Self {
+            interval: Duration::from_millis(var("MATCH_INTERVAL_MS", 10_000)?),
+            debounce: Duration::from_millis(var("MATCH_DEBOUNCE_MS", 250)?),
+            max_fills: usize::try_from(var("MATCH_MAX_FILLS", 32)?)?,
+            max_backoff: Duration::from_millis(var("MATCH_MAX_BACKOFF_MS", 300_000)?),
+            max_attempts: u32::try_from(var("MATCH_MAX_ATTEMPTS", 3)?)?,
+        };
+        ensure!(
+            !config.interval.is_zero(),
+            "MATCH_INTERVAL_MS must be greater than zero"
+        );
+        ensure!(
+            config.max_fills > 0,
+            "MATCH_MAX_FILLS must be greater than zero"
+        );
+        ensure!(
+            config.max_attempts > 0,
+            "MATCH_MAX_ATTEMPTS must be greater than zero"
+        );
+        Ok(config)
+    }
+}
+
+impl Retry {
+    /// Records the outcome of a round.
+    ///
+    /// Returns the bid and ask to evict once their fill has failed
+    /// `max_attempts` rounds in a row.
+    fn record(&mut self, matched: &Matched, max_attempts: u32) -> Option<(OrderId, OrderId)> {
+        let Some(failed) = matched.failed else {
+            *self = Self::default();
+            return None;
+        };
+        self.failures = self.failures.saturating_add(1);
+        let attempts = match self.stuck {
+            Some((fill, attempts)) if fill == failed => attempts + 1,
+            _ => 1,
+        };
+        if attempts >= max_attempts {
+            self.stuck = None;
+            return Some(failed);
+        }
+        self.stuck = Some((failed, attempts));
+        None
+    }
+
+    /// How long to wait before the next round, doubling with each failed
+    /// round up to the config's max backoff.
+    fn backoff(&self, config: &Config) -> Duration {
+        if self.failures == 0 {
+            return Duration::ZERO;
+        }
+        let doublings = (self.failures - 1).min(16);
+        config
+            .interval
+            .saturating_mul(1 << doublings)
+            .min(config.max_backoff)
+    }
+}
+
+/// Wakes the background loop if the book crosses.
+pub fn poke(order_book: &OrderBook, wake: &Notify) {
+    if let (Some(bid), Some(ask)) = (order_book.best_bid(), order_book.best_ask()) {
+        if bid >= ask {
+            wake.notify_one();
+        }
+    }
+}
+
+/// Matches the book of the market and settles up to `max_fills` fills.
+///
+/// The book keeps its pre-match state until each fill is included.
+pub async fn round(market: &Market, node: &EssentialNodeClient, max_fills: usize) -> Matched {
+    let Market {
+        book: order_book,
+        settlement,
+        metrics,
+        ..
+    } = market;
+    let start = Instant::now();
+    // One settlement at a time, so a fill is never submitted twice
+    let mut settlement = settlement.lock().await;
+    // Only match orders that still agree with their contracts
+    let pruned = chain::prune(node, order_book).await;
+    if !pruned.is_empty() {
+        let order_book = order_book.lock().unwrap();
+        for order in &pruned {
+            market.persist(&order_book, order.id);
+            market.feed.level(&order_book, order.side, order.price);
+        }
+    }
+    // Match a copy so the book keeps its pre-match state until a fill is included
+    let mut fills = order_book.lock().unwrap().clone().match_orders();
+    let matched_count = fills.len();
+    fills.truncate(max_fills);
+    let mut matched = Matched {
+        fills: Vec::with_capacity(fills.len()),
+        error: None,
+        failed: None,
+    };
+    for fill in fills {
+        match settlement.settle(&fill).await {
+            Ok(solution) => {
+                let mut order_book = order_book.lock().unwrap();
+                for order in [&fill.bid, &fill.ask] {
+                    order_book.reduce(order.id, fill.quantity);
+                    market.persist(&order_book, order.id);
+                    market.feed.level(&order_book, order.side, order.price);
+                }
+                let price = settle::fill_price(&fill);
+                let Fill { bid, ask, quantity } = fill;
+                let settled = Settled {
+                    price,
+                    quantity,
+                    bid,
+                    ask,
+                    solution,
+                };
+                market.record(&settled);
+                matched.fills.push(settled);
+            }
+            Err(err) => {
+                // Later fills may rely on this one, so stop here
+                matched.error = Some(format!("{:#}", err));
+                matched.failed = Some((fill.bid.id, fill.ask.id));
+                break;
+            }
+        }
+    }
+
+    let round = Round {
+        millis: start.elapsed().as_millis(),
+        matched: matched_count,
+        settled: matched.fills.len(),
+        error: matched.error.clone(),
+    };
+    let mut metrics = metrics.lock().unwrap();
+    metrics.rounds += 1;
+    metrics.fills += round.settled as u64;
+    metrics.failures += u64::from(round.error.is_some());
+    metrics.pruned += pruned.len() as u64;
+    metrics.last = Some(round);
+    matched
+}
+
+/// Runs rounds forever, whenever the book crosses or the interval passes.
+pub async fn run(config: Config, market: Arc<Market>, node: Arc<EssentialNodeClient>) {
+    let mut retry = Retry::default();
+    loop {
+        let backoff = retry.backoff(&config);
+        if backoff.is_zero() {
+            tokio::select! {
+                _ = market.wake.notified() => (),
+                _ = actix_web::rt::time::sleep(config.interval) => (),
+            }
+        } else {
+            // Orders added during the backoff don't cut it short
+            actix_web::rt::time::sleep(backoff).await;
+        }
+        debounce(&market.wake, config.debounce, config.interval).await;
+        if market.book.lock().unwrap().is_empty() {
+            continue;
+        }
+        let matched = round(&market, &node, config.max_fills).await;
+        if let Some((bid, ask)) = retry.record(&matched, config.max_attempts) {
+            evict(&market, [bid, ask]);
+        }
+        match &matched.error {
+            // Fills past the limit are left for another round right away
+            None => poke(&market.book.lock().unwrap(), &market.wake),
+            Some(err) => eprintln!(
+                "Matching {} stopped after {} fills: {}",
+                market.pair,
+                matched.fills.len(),
+                err
+            ),
+        }
+    }
+}
+
+/// Waits until no wake has come for the debounce period, or at most `limit`.
+///
+/// Each wake restarts the debounce period, and the limit stops a steady
+/// stream of orders from putting matching off forever.
+async fn debounce(wake: &Notify, period: Duration, limit: Duration) {
+    let deadline = Instant::now() + limit;
+    loop {
+        let wait = period.min(deadline.saturating_duration_since(Instant::now()));
+        if wait.is_zero() {
+            return;
+        }
+        tokio::select! {
+            _ = wake.notified() => (),
+            _ = actix_web::rt::time::sleep(wait) => return,
+        }
+    }
+}
+
+/// Removes the orders of a fill that keeps failing to settle.
+fn evict(market: &Market, ids: [OrderId; 2]) {
+    let mut order_book = market.book.lock().unwrap();
+    let mut evicted = 0;
+    for id in ids {
+        let Some(order) = order_book.cancel(id) else {
+            continue;
+        };
+        eprintln!(
+            "Evicted order {} of {} after it failed to settle",
+            id, market.pair
+        );
+        market.persist(&order_book, id);
+        market.feed.level(&order_book, order.side, order.price);
+        evicted += 1;
+    }
+    drop(order_book);
+    market.metrics.lock().unwrap().evicted += evicted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> anyhow::Result<Config> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Config::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    fn matched(failed: Option<(OrderId, OrderId)>) -> Matched {
+        Matched {
+            fills: vec![],
+            error: failed.map(|_| "failed".to_string()),
+            failed,
+        }
+    }
+
+    #[test]
+    fn reads_config_with_defaults() {
+        let defaults = config(&[]).unwrap();
+        assert_eq!(defaults.interval, Duration::from_secs(10));
+        assert_eq!(defaults.debounce, Duration::from_millis(250));
+        assert_eq!(defaults.max_fills, 32);
+        assert_eq!(defaults.max_backoff, Duration::from_secs(300));
+        assert_eq!(defaults.max_attempts, 3);
+
+        let set = config(&[("MATCH_MAX_FILLS", "5"), ("MATCH_DEBOUNCE_MS", "0")]).unwrap();
+        assert_eq!(set.max_fills, 5);
+        assert_eq!(set.debounce, Duration::ZERO);
+    }
+
+    #[test]
+    fn rejects_config_that_never_makes_progress() {
+        assert!(config(&[("MATCH_MAX_FILLS", "0")]).is_err());
+        assert!(config(&[("MATCH_INTERVAL_MS", "0")]).is_err());
+        assert!(config(&[("MATCH_MAX_ATTEMPTS", "0")]).is_err());
+        assert!(config(&[("MATCH_MAX_FILLS", "many")]).is_err());
+    }
+
+    #[test]
+    fn backs_off_until_a_round_succeeds() {
+        let config = Config {
+            max_backoff: Duration::from_secs(35),
+            max_attempts: 10,
+            ..config(&[]).unwrap()
+        };
+        let mut retry = Retry::default();
+        assert_eq!(retry.backoff(&config), Duration::ZERO);
+        let mut backoffs = vec![];
+        for _ in 0..4 {
+            retry.record(&matched(Some((0, 1))), config.max_attempts);
+            backoffs.push(retry.backoff(&config).as_secs());
+        }
+        assert_eq!(backoffs, [10, 20, 35, 35]);
+        retry.record(&matched(None), config.max_attempts);
+        assert_eq!(retry.backoff(&config), Duration::ZERO);
+    }
+
+    #[test]
+    fn evicts_a_fill_that_keeps_failing() {
+        let mut retry = Retry::default();
+        assert_eq!(retry.record(&matched(Some((0, 1))), 3), None);
+        assert_eq!(retry.record(&matched(Some((0, 1))), 3), None);
+        assert_eq!(retry.record(&matched(Some((0, 1))), 3), Some((0, 1)));
+
+        // Another fill failing, or a round settling, starts the count again
+        assert_eq!(retry.record(&matched(Some((0, 1))), 3), None);
+        assert_eq!(retry.record(&matched(Some((2, 1))), 3), None);
+        assert_eq!(retry.record(&matched(None), 3), None);
+        assert_eq!(retry.record(&matched(Some((2, 1))), 3), None);
+        assert_eq!(retry.record(&matched(Some((2, 1))), 3), None);
+        assert_eq!(retry.record(&matched(Some((2, 1))), 3), Some((2, 1)));
+    }
+
+    #[actix_web::test]
+    async fn pokes_only_when_the_book_crosses() {
+        let wake = Notify::new();
+        let mut book = OrderBook::new();
+        let addresses = || Addresses {
+            addresses: vec![],
+            nonce: 0,
+        };
+        book.add_bid([1; 4], 99, 1, addresses());
+        book.add_ask([2; 4], 100, 1, addresses());
+        poke(&book, &wake);
+        let woken = actix_web::rt::time::timeout(Duration::from_millis(10), wake.notified());
+        assert!(woken.await.is_err());
+
+        book.add_bid([1; 4], 100, 1, addresses());
+        poke(&book, &wake);
+        let woken = actix_web::rt::time::timeout(Duration::from_millis(10), wake.notified());
+        assert!(woken.await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn debounce_restarts_on_each_wake() {
+        let wake = Arc::new(Notify::new());
+        let period = Duration::from_millis(50);
+        let waker = wake.clone();
+        actix_web::rt::spawn(async move {
+            for _ in 0..3 {
+                actix_web::rt::time::sleep(Duration::from_millis(30)).await;
+                waker.notify_one();
+            }
+        });
+        let start = Instant::now();
+        debounce(&wake, period, Duration::from_secs(10)).await;
+        // The last wake came at 90ms, so the debounce ends 50ms after it
+        assert!(start.elapsed() >= Duration::from_millis(130));
+
+        // A steady stream of wakes can't hold it past the limit
+        let start = Instant::now();
+        let waker = wake.clone();
+        let stream = actix_web::rt::spawn(async move {
+            loop {
+                actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+                waker.notify_one();
+            }
+        });
+        debounce(&wake, period, Duration::from_millis(100)).await;
+        stream.abort();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,288 @@
+//! # Settle
+//! Turns fills into solutions and submits them to a builder.
+//!
+//! A fill is settled by one solution holding the `Bid` predicate of the bid's
+//! order contract, the `Ask` predicate of the ask's order contract, token A
+//! sent from the ask owner to the bid owner and token B sent back. Either all
+//! of it is included or none of it is.
+//!
+//! Both orders trade at the fill price, the price of the order that was in
+//! the book first. The `Bid` predicate checks it is no more than the bid's
+//! price and the `Ask` predicate that it is no less than the ask's, so a
+//! crossed book settles like any other.
+//!
+//! The token transfers must be signed by their owners. Each owner runs a
+//! signing service (see [`token::signer::RemoteSigner`]) and the backend is
+//! given a directory holding their sockets.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::web;
+use anyhow::{bail, ensure, Context};
+use essential_rest_client::{
+    builder_client::EssentialBuilderClient, node_client::EssentialNodeClient,
+};
+use essential_types::{solution::Solution, ContentAddress, Key, PredicateAddress, Value, Word};
+use matching_engine::{Fill, Owner};
+use order_app::deploy::Pair;
+use token::signer::{RemoteSigner, Signer};
+
+use crate::Addresses;
+
+/// How often the node is polled while waiting for a solution to be included.
+const POLL: Duration = Duration::from_secs(1);
+
+/// Signs token transfers for the owners that registered a signing service.
+///
+/// Each signer has its own lock, so a slow signing service only holds up
+/// transfers from its owner.
+#[derive(Default)]
+pub struct Signers(HashMap<Owner, Arc<Mutex<RemoteSigner>>>);
+
+/// Everything needed to settle the fills of one market.
+///
+/// The clients and signers are shared by every market.
+pub struct Settlement {
+    /// Reads the order and token state.
+    pub node: Arc<EssentialNodeClient>,
+    /// Receives the settlement solutions.
+    pub builder: Arc<EssentialBuilderClient>,
+    /// The tokens the orders trade.
+    pub pair: Pair,
+    /// Signs the token transfers.
+    pub signers: Arc<Mutex<Signers>>,
+    /// How long to wait for a solution to be included.
+    pub timeout: Duration,
+}
+
+impl Signers {
+    /// Opens every signing service socket in the directory.
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        let mut signers = HashMap::new();
+        for entry in std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read signers from {}", dir.display()))?
+        {
+            let mut signer = RemoteSigner::new(entry?.path());
+            signers.insert(signer.hashed_key()?, Arc::new(Mutex::new(signer)));
+        }
+        Ok(Self(signers))
+    }
+
+    /// Whether the owner registered a signing service.
+    pub fn contains(&self, owner: Owner) -> bool {
+        self.0.contains_key(&owner)
+    }
+
+    /// The signer of the owner.
+    fn get(&self, owner: Owner) -> anyhow::Result<Arc<Mutex<RemoteSigner>>> {
+        let Some(signer) = self.0.get(&owner) else {
+            bail!("No signer for owner {:?}", owner)
+        };
+        Ok(signer.clone())
+    }
+}
+
+impl Settlement {
+    /// Settles a fill and waits until it is included.
+    ///
+    /// Returns the address of the settlement solution.
+    pub async fn settle(&mut self, fill: &Fill<Addresses>) -> anyhow::Result<ContentAddress> {
+        let (solution, bid_left) = self.build(fill).await?;
+        let ca = self.builder.submit_solution(&solution).await?;
+        let bid = predicate(&fill.bid.data)?;
+        self.wait(&bid.contract, bid_left)
+            .await
+            .with_context(|| format!("Settlement {} was not included", ca))?;
+        Ok(ca)
+    }
+
+    /// Builds the solution that settles the fill.
+    ///
+    /// Also returns the amount the bid has left once it is included.
+    async fn build(&mut self, fill: &Fill<Addresses>) -> anyhow::Result<(Solution, Word)> {
+        let Fill { bid, ask, quantity } = fill;
+        let price = fill_price(fill);
+        let payment = payment(*quantity, price)?;
+        let bid_predicate = predicate(&bid.data)?;
+        let ask_predicate = predicate(&ask.data)?;
+
+        let (bid_amount, bid_nonce) = self.order_state(&bid_predicate.contract).await?;
+        let bid_price = self
+            .query(&bid_predicate.contract, order_app::price_key())
+            .await?;
+        let bid_fill = order_app::bid::init(order_app::bid::Init {
+            predicate: bid_predicate,
+            amount: *quantity,
+            fill_price: price,
+            bid_amount: order_app::Query(bid_amount),
+            price: order_app::Query(bid_price),
+            nonce: order_app::Query(bid_nonce),
+            instance_nonce: bid.data.nonce,
+        })?;
+        let (ask_amount, ask_nonce) = self.order_state(&ask_predicate.contract).await?;
+        let ask_price = self
+            .query(&ask_predicate.contract, order_app::price_key())
+            .await?;
+        let ask_fill = order_app::ask::init(order_app::ask::Init {
+            predicate: ask_predicate,
+            amount: *quantity,
+            fill_price: price,
+            bid_amount: order_app::Query(ask_amount),
+            price: order_app::Query(ask_price),
+            nonce: order_app::Query(ask_nonce),
+            instance_nonce: ask.data.nonce,
+        })?;
+
+        let bid_left = bid_fill.new_bid_amount;
+        let token_a = self.pair.token_a.clone();
+        let token_b = self.pair.token_b.clone();
+        let mut solution = Solution { data: vec![] };
+        solution
+            .data
+            .extend(order_app::bid::build_solution(bid_fill)?.data);
+        solution
+            .data
+            .extend(order_app::ask::build_solution(ask_fill)?.data);
+        solution.data.extend(
+            self.transfer(&token_a, ask.owner, bid.owner, *quantity)
+                .await?
+                .data,
+        );
+        solution.data.extend(
+            self.transfer(&token_b, bid.owner, ask.owner, payment)
+                .await?
+                .data,
+        );
+        Ok((solution, bid_left))
+    }
+
+    /// Builds a token transfer signed by the sender's signing service.
+    async fn transfer(
+        &mut self,
+        token: &ContentAddress,
+        from: Owner,
+        to: Owner,
+        amount: Word,
+    ) -> anyhow::Result<Solution> {
+        let nonce = self.query(token, token::nonce_key(from)).await?;
+        let to_sign = token::transfer::data_to_sign(token::transfer::Init {
+            hashed_from_key: from,
+            hashed_to_key: to,
+            amount,
+            nonce: token::Query(nonce.clone()),
+        })?;
+        // Signing waits on the signing service, so it runs off the async workers
+        // without holding the lock on the other signers.
+        let signer = self.signers.lock().unwrap().get(from)?;
+        let words = to_sign.to_words();
+        let signature = web::block(move || signer.lock().unwrap().sign_words(&words)).await??;
+        let from_balance = self.query(token, token::balance_key(from)).await?;
+        let to_balance = self.query(token, token::balance_key(to)).await?;
+        let solution = token::transfer::build_solution(token::transfer::BuildSolution {
+            token: token.clone(),
+            hashed_from_key: from,
+            hashed_to_key: to,
+            new_nonce: to_sign.new_nonce,
+            amount,
+            current_nonce: token::Query(nonce),
+            current_from_balance: token::Query(from_balance),
+            current_to_balance: token::Query(to_balance),
+            signature,
+        })?;
+        Ok(solution)
+    }
+
+    /// Waits until the order has the amount left it has after the fill.
+    async fn wait(&self, contract: &ContentAddress, amount: Word) -> anyhow::Result<()> {
+        let mut waited = Duration::ZERO;
+        loop {
+            let (left, _) = self.order_state(contract).await?;
+            if order_app::bid_amount(order_app::Query(left))? == amount {
+                return Ok(());
+            }
+            ensure!(waited < self.timeout, "Timed out after {:?}", waited);
+            actix_web::rt::time::sleep(POLL).await;
+            waited += POLL;
+        }
+    }
+
+    /// Queries the remaining amount and nonce of an order contract instance.
+    async fn order_state(
+        &self,
+        contract: &ContentAddress,
+    ) -> anyhow::Result<(Option<Value>, Option<Value>)> {
+        let amount = self.query(contract, order_app::bid_amount_key()).await?;
+        let nonce = self.query(contract, order_app::nonce_key()).await?;
+        Ok((amount, nonce))
+    }
+
+    async fn query(&self, contract: &ContentAddress, key: Key) -> anyhow::Result<Option<Value>> {
+        Ok(self.node.query_state(contract.clone(), key).await?)
+    }
+}
+
+/// The price a fill trades at, the price of the order that was in the book first.
+///
+/// The book only matches a bid at or above an ask, so this is never above the
+/// bid's price or below the ask's.
+pub fn fill_price<T>(fill: &Fill<T>) -> Word {
+    if fill.bid.sequence < fill.ask.sequence {
+        fill.bid.price
+    } else {
+        fill.ask.price
+    }
+}
+
+/// The amount of token B paid for the quantity of token A at the price.
+fn payment(quantity: Word, price: Word) -> anyhow::Result<Word> {
+    quantity
+        .checked_mul(price)
+        .with_context(|| format!("Paying for {} at {} overflows", quantity, price))
+}
+
+/// The `Bid` or `Ask` predicate of an order, which is the first address.
+fn predicate(data: &Addresses) -> anyhow::Result<PredicateAddress> {
+    crate::chain::contract(&data.addresses)?;
+    Ok(data.addresses[0].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matching_engine::OrderBook;
+
+    #[test]
+    fn fills_at_the_price_of_the_first_order() {
+        // The ask rested first, so the later, higher bid buys at the ask's price
+        let mut book = OrderBook::new();
+        book.add_ask([2; 4], 100, 5, ());
+        book.add_bid([1; 4], 105, 5, ());
+        let fills = book.match_orders();
+        assert_eq!(fill_price(&fills[0]), 100);
+
+        // The bid rested first, so the later, lower ask sells at the bid's price
+        let mut book = OrderBook::new();
+        book.add_bid([1; 4], 105, 5, ());
+        book.add_ask([2; 4], 100, 5, ());
+        let fills = book.match_orders();
+        assert_eq!(fill_price(&fills[0]), 105);
+    }
+
+    #[test]
+    fn payment_overflow_is_an_error() {
+        assert_eq!(payment(5, 100).unwrap(), 500);
+        assert!(payment(Word::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn only_registered_owners_can_sign() {
+        let signers = Signers(HashMap::new());
+        assert!(!signers.contains([1; 4]));
+        assert!(signers.get([1; 4]).is_err());
+    }
+}
@@ -0,0 +1,671 @@
+//! # Storage
+//! Persists the markets, their orders and trades so they survive a restart.
+//!
+//! The backend uses [`SledStore`], an embedded database on disk.
+//! `MemoryStore` keeps everything in memory for tests.
+//!
+//! Orders are saved as they are in the book after every change, along with
+//! the book's counters, so a restored book never reuses an order ID. Trades
+//! are saved with the candle buckets they changed, see [`crate::trades`].
+//!
+//! Each market has a [`Writer`] that queues its changes in the order the book
+//! made them. Changes are applied off the async workers, and everything queued
+//! while a write runs is applied together, in one transaction with one flush.
+
+use std::{path::Path, sync::Arc};
+
+use actix_web::web;
+use anyhow::Context;
+use matching_engine::{Order, OrderId, Sequence};
+use order_app::deploy::Pair;
+use serde::{Deserialize, Serialize};
+use sled::Transactional;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    trades::{Bucket, History, Interval, Trade, INTERVALS, MAX_CANDLES, RECENT_TRADES},
+    Addresses, OrderBook,
+};
+
+/// The ID and sequence a book gives its next order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counters {
+    /// The ID given to the next order.
+    pub next_id: OrderId,
+    /// The sequence given to the next order.
+    pub next_sequence: Sequence,
+}
+
+/// A market as it was saved.
+#[derive(Clone)]
+pub struct Saved {
+    /// The tokens the market trades.
+    pub pair: Pair,
+    /// The resting orders.
+    pub orders: Vec<Order<Addresses>>,
+    /// The book's counters.
+    pub counters: Counters,
+    /// The latest [`RECENT_TRADES`] trades, oldest first.
+    pub trades: Vec<Trade>,
+    /// The ID given to the next trade.
+    pub next_trade: u64,
+    /// The latest [`MAX_CANDLES`] candle buckets of each interval, oldest first.
+    pub candles: Vec<(Interval, Bucket)>,
+}
+
+/// A change to a market to persist.
+#[derive(Clone)]
+pub enum Change {
+    /// An order as it is now, along with the book's counters.
+    Order(Order<Addresses>, Counters),
+    /// An order left the book.
+    Removed(OrderId),
+    /// A trade was recorded.
+    Trade(Trade),
+    /// A trade changed the candle of an interval.
+    Candle(Interval, Bucket),
+}
+
+/// Somewhere the markets are persisted.
+///
+/// These calls block, so async code goes through a [`Writer`] or `web::block`.
+pub trait Store: Send + Sync {
+    /// Records a market. Recording an existing market does nothing.
+    fn add_market(&self, pair: &Pair) -> anyhow::Result<()>;
+
+    /// Applies the changes to the market in order.
+    /// Either every change is saved or none are.
+    fn apply(&self, pair: &Pair, changes: &[Change]) -> anyhow::Result<()>;
+
+    /// Loads every market, with only the trades and candles a [`History`] keeps.
+    fn load(&self) -> anyhow::Result<Vec<Saved>>;
+}
+
+/// Queues the changes of one market for its write task.
+pub struct Writer(mpsc::UnboundedSender<Write>);
+
+/// Changes queued together, and who to tell once they are saved.
+struct Write {
+    changes: Vec<Change>,
+    done: Option<oneshot::Sender<Result<(), String>>>,
+}
+
+/// Stores the markets in a sled database.
+///
+/// Each tree is keyed by market ID:
+/// - `markets`: the market ID to its counters.
+/// - `orders`: `<market ID>/<order ID>` to the order.
+/// - `trades`: `<market ID>/<trade ID>` to the trade.
+/// - `next_trades`: the market ID to the ID given to its next trade.
+/// - `candles`: `<market ID>/<interval><start>` to the candle bucket.
+pub struct SledStore {
+    db: sled::Db,
+    markets: sled::Tree,
+    orders: sled::Tree,
+    trades: sled::Tree,
+    next_trades: sled::Tree,
+    candles: sled::Tree,
+}
+
+/// Stores the markets in memory.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore(std::sync::Mutex<MemoryMarkets>);
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryMarkets {
+    markets: std::collections::BTreeMap<Pair, MemoryMarket>,
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryMarket {
+    counters: Counters,
+    orders: std::collections::BTreeMap<OrderId, Order<Addresses>>,
+    trades: Vec<Trade>,
+    next_trade: u64,
+    candles: std::collections::BTreeMap<(usize, u64), (Interval, Bucket)>,
+}
+
+impl Counters {
+    /// The counters of the book.
+    pub fn of(book: &OrderBook) -> Self {
+        Self {
+            next_id: book.next_id(),
+            next_sequence: book.next_sequence(),
+        }
+    }
+}
+
+/// The change that saves the order with the ID as it is in the book,
+/// or removes it if it has left the book.
+pub fn change(book: &OrderBook, id: OrderId) -> Change {
+    match book.get(id) {
+        Some(order) => Change::Order(order.clone(), Counters::of(book)),
+        None => Change::Removed(id),
+    }
+}
+
+impl Writer {
+    /// Starts the write task of the pair's market.
+    ///
+    /// Must be called from within the actix runtime.
+    pub fn spawn(store: Arc<dyn Store>, pair: Pair) -> Self {
+        let (sender, queue) = mpsc::unbounded_channel();
+        actix_web::rt::spawn(write(store, pair, queue));
+        Self(sender)
+    }
+
+    /// Queues the change. A failure to save it is logged.
+    pub fn send(&self, change: Change) {
+        self.send_all(vec![change]);
+    }
+
+    /// Queues the changes to be saved together. A failure to save them is logged.
+    pub fn send_all(&self, changes: Vec<Change>) {
+        let write = Write {
+            changes,
+            done: None,
+        };
+        if self.0.send(write).is_err() {
+            eprintln!("Failed to save a change: the write task has stopped");
+        }
+    }
+
+    /// Queues the change and waits until it is saved.
+    pub async fn save(&self, change: Change) -> anyhow::Result<()> {
+        let (done, saved) = oneshot::channel();
+        let write = Write {
+            changes: vec![change],
+            done: Some(done),
+        };
+        self.0
+            .send(write)
+            .map_err(|_| anyhow::anyhow!("The write task has stopped"))?;
+        saved
+            .await
+            .context("The write task has stopped")?
+            .map_err(anyhow::Error::msg)
+    }
+}
+
+/// Applies the queued changes in order until every writer is dropped.
+async fn write(store: Arc<dyn Store>, pair: Pair, mut queue: mpsc::UnboundedReceiver<Write>) {
+    while let Some(first) = queue.recv().await {
+        let mut writes = vec![first];
+        while let Ok(write) = queue.try_recv() {
+            writes.push(write);
+        }
+        let changes: Vec<Change> = writes
+            .iter_mut()
+            .flat_map(|write| std::mem::take(&mut write.changes))
+            .collect();
+        let (store, batch) = (store.clone(), pair.clone());
+        let result = match web::block(move || store.apply(&batch, &changes)).await {
+            Ok(result) => result,
+            Err(err) => Err(err.into()),
+        }
+        .map_err(|err| format!("{:#}", err));
+        if let Err(err) = &result {
+            eprintln!("Failed to save changes to {}: {}", pair, err);
+        }
+        for done in writes.into_iter().filter_map(|write| write.done) {
+            let _ = done.send(result.clone());
+        }
+    }
+}
+
+/// Restores a book and its trade history from what was saved.
+pub fn restore(saved: Saved) -> (Pair, OrderBook, History) {
+    let Saved {
+        pair,
+        orders,
+        counters,
+        trades,
+        next_trade,
+        candles,
+    } = saved;
+    let book = OrderBook::restore(orders, counters.next_id, counters.next_sequence);
+    (pair, book, History::restore(trades, candles, next_trade))
+}
+
+impl SledStore {
+    /// Opens or creates the database at the path.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let db = sled::open(path)
+            .with_context(|| format!("Failed to open store at {}", path.display()))?;
+        Self::new(db)
+    }
+
+    /// Opens a database that is deleted when dropped.
+    #[cfg(test)]
+    pub fn temporary() -> anyhow::Result<Self> {
+        Self::new(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn new(db: sled::Db) -> anyhow::Result<Self> {
+        Ok(Self {
+            markets: db.open_tree("markets")?,
+            orders: db.open_tree("orders")?,
+            trades: db.open_tree("trades")?,
+            next_trades: db.open_tree("next_trades")?,
+            candles: db.open_tree("candles")?,
+            db,
+        })
+    }
+}
+
+impl Store for SledStore {
+    fn add_market(&self, pair: &Pair) -> anyhow::Result<()> {
+        let counters = serde_json::to_vec(&Counters::default())?;
+        // Keep the counters of a market that already exists.
+        let _ = self
+            .markets
+            .compare_and_swap(market_key(pair), None::<&[u8]>, Some(counters))?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn apply(&self, pair: &Pair, changes: &[Change]) -> anyhow::Result<()> {
+        // Encode up front, as the transaction may run more than once
+        let mut counters = None;
+        let mut orders = Vec::new();
+        let mut trades = Vec::new();
+        let mut next_trade = None;
+        let mut candles = Vec::new();
+        for change in changes {
+            match change {
+                Change::Order(order, now) => {
+                    counters = Some(serde_json::to_vec(now)?);
+                    orders.push((item_key(pair, order.id), Some(serde_json::to_vec(order)?)));
+                }
+                Change::Removed(id) => orders.push((item_key(pair, *id), None)),
+                Change::Trade(trade) => {
+                    next_trade = next_trade.max(Some(trade.id + 1));
+                    trades.push((item_key(pair, trade.id), serde_json::to_vec(trade)?))
+                }
+                Change::Candle(interval, bucket) => candles.push((
+                    candle_key(pair, *interval, bucket.start()),
+                    serde_json::to_vec(bucket)?,
+                )),
+            }
+        }
+        let result: sled::transaction::TransactionResult<()> = (
+            &self.markets,
+            &self.orders,
+            &self.trades,
+            &self.next_trades,
+            &self.candles,
+        )
+            .transaction(
+                |(markets, orders_tree, trades_tree, next_trades, candles_tree)| {
+                    if let Some(counters) = &counters {
+                        markets.insert(market_key(pair), counters.as_slice())?;
+                    }
+                    if let Some(next_trade) = next_trade {
+                        next_trades.insert(market_key(pair), &next_trade.to_be_bytes())?;
+                    }
+                    for (key, order) in &orders {
+                        match order {
+                            Some(order) => orders_tree.insert(key.as_slice(), order.as_slice())?,
+                            None => orders_tree.remove(key.as_slice())?,
+                        };
+                    }
+                    for (key, trade) in &trades {
+                        trades_tree.insert(key.as_slice(), trade.as_slice())?;
+                    }
+                    for (key, bucket) in &candles {
+                        candles_tree.insert(key.as_slice(), bucket.as_slice())?;
+                    }
+                    Ok(())
+                },
+            );
+        result?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<Saved>> {
+        let mut saved = Vec::new();
+        for entry in self.markets.iter() {
+            let (key, counters) = entry?;
+            let pair: Pair = std::str::from_utf8(&key)?.parse()?;
+            let mut orders = Vec::new();
+            for entry in self.orders.scan_prefix(prefix(&pair)) {
+                let (_, order) = entry?;
+                orders.push(serde_json::from_slice(&order)?);
+            }
+            // Scan back from the latest, as only the latest trades and candles are kept
+            let mut trades = Vec::new();
+            for entry in self
+                .trades
+                .scan_prefix(prefix(&pair))
+                .rev()
+                .take(RECENT_TRADES)
+            {
+                let (_, trade) = entry?;
+                trades.push(serde_json::from_slice(&trade)?);
+            }
+            trades.reverse();
+            let mut candles = Vec::new();
+            for interval in INTERVALS {
+                let mut buckets = Vec::new();
+                for entry in self
+                    .candles
+                    .scan_prefix(interval_prefix(&pair, interval))
+                    .rev()
+                    .take(MAX_CANDLES)
+                {
+                    let (_, bucket) = entry?;
+                    buckets.push((interval, serde_json::from_slice(&bucket)?));
+                }
+                candles.extend(buckets.into_iter().rev());
+            }
+            let next_trade = match self.next_trades.get(market_key(&pair))? {
+                Some(next) => u64::from_be_bytes(next.as_ref().try_into()?),
+                None => 0,
+            };
+            saved.push(Saved {
+                pair,
+                orders,
+                counters: serde_json::from_slice(&counters)?,
+                trades,
+                next_trade,
+                candles,
+            });
+        }
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+impl Store for MemoryStore {
+    fn add_market(&self, pair: &Pair) -> anyhow::Result<()> {
+        let mut store = self.0.lock().unwrap();
+        store.markets.entry(pair.clone()).or_default();
+        Ok(())
+    }
+
+    fn apply(&self, pair: &Pair, changes: &[Change]) -> anyhow::Result<()> {
+        let mut store = self.0.lock().unwrap();
+        let market = store.markets.entry(pair.clone()).or_default();
+        for change in changes {
+            match change {
+                Change::Order(order, counters) => {
+                    market.counters = *counters;
+                    market.orders.insert(order.id, order.clone());
+                }
+                Change::Removed(id) => {
+                    market.orders.remove(id);
+                }
+                Change::Trade(trade) => {
+                    market.next_trade = market.next_trade.max(trade.id + 1);
+                    market.trades.push(trade.clone());
+                }
+                Change::Candle(interval, bucket) => {
+                    let key = (interval.index(), bucket.start());
+                    market.candles.insert(key, (*interval, bucket.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<Saved>> {
+        let store = self.0.lock().unwrap();
+        Ok(store
+            .markets
+            .iter()
+            .map(|(pair, market)| Saved {
+                pair: pair.clone(),
+                orders: market.orders.values().cloned().collect(),
+                counters: market.counters,
+                trades: market.trades[market.trades.len().saturating_sub(RECENT_TRADES)..].to_vec(),
+                next_trade: market.next_trade,
+                candles: INTERVALS
+                    .into_iter()
+                    .flat_map(|interval| {
+                        let index = interval.index();
+                        let buckets: Vec<_> = market
+                            .candles
+                            .range((index, 0)..(index + 1, 0))
+                            .map(|(_, bucket)| bucket.clone())
+                            .collect();
+                        let kept = buckets.len().saturating_sub(MAX_CANDLES);
+                        buckets.into_iter().skip(kept)
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+fn market_key(pair: &Pair) -> Vec<u8> {
+    pair.to_string().into_bytes()
+}
+
+/// The prefix of every order or trade key of a market.
+fn prefix(pair: &Pair) -> Vec<u8> {
+    let mut key = market_key(pair);
+    key.push(b'/');
+    key
+}
+
+/// Big endian IDs keep the keys of a market in ID order.
+fn item_key(pair: &Pair, id: u64) -> Vec<u8> {
+    let mut key = prefix(pair);
+    key.extend(id.to_be_bytes());
+    key
+}
+
+/// The prefix of every candle key of an interval of a market.
+fn interval_prefix(pair: &Pair, interval: Interval) -> Vec<u8> {
+    let mut key = prefix(pair);
+    key.push(interval.index() as u8);
+    key
+}
+
+/// Big endian start times keep the candles of an interval in time order.
+fn candle_key(pair: &Pair, interval: Interval, start: u64) -> Vec<u8> {
+    let mut key = interval_prefix(pair, interval);
+    key.extend(start.to_be_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trades::TradeSide;
+    use essential_types::ContentAddress;
+
+    fn pair(token: u8) -> Pair {
+        Pair::new(ContentAddress([token; 32]), ContentAddress([0xFF; 32])).unwrap()
+    }
+
+    fn addresses() -> Addresses {
+        Addresses::default()
+    }
+
+    fn trade(id: u64, timestamp: u64) -> Trade {
+        Trade {
+            id,
+            timestamp,
+            price: 100,
+            quantity: 1,
+            bid: TradeSide {
+                order: 0,
+                owner: [1; 4],
+            },
+            ask: TradeSide {
+                order: 1,
+                owner: [2; 4],
+            },
+            solution: ContentAddress([0; 32]),
+        }
+    }
+
+    // Adds, fills and cancels orders, then restores the book from the store
+    fn restores_books(store: &dyn Store) {
+        let (a, b) = (pair(1), pair(2));
+        store.add_market(&a).unwrap();
+        store.add_market(&b).unwrap();
+
+        let mut book = OrderBook::new();
+        let bid = book.add_bid([1; 4], 100, 10, addresses());
+        let cancelled = book.add_ask([2; 4], 105, 5, addresses());
+        let ask = book.add_ask([2; 4], 100, 4, addresses());
+        let added: Vec<_> = [bid, cancelled, ask]
+            .into_iter()
+            .map(|id| change(&book, id))
+            .collect();
+        store.apply(&a, &added).unwrap();
+        book.cancel(cancelled).unwrap();
+        store.apply(&a, &[change(&book, cancelled)]).unwrap();
+        book.reduce(bid, 4).unwrap();
+        book.reduce(ask, 4).unwrap();
+        store
+            .apply(&a, &[change(&book, bid), change(&book, ask)])
+            .unwrap();
+        let trades: Vec<_> = (0..2)
+            .map(|id| Trade {
+                id,
+                timestamp: 1_000 + id,
+                price: 100,
+                quantity: 2,
+                bid: TradeSide {
+                    order: bid,
+                    owner: [1; 4],
+                },
+                ask: TradeSide {
+                    order: ask,
+                    owner: [2; 4],
+                },
+                solution: ContentAddress([0; 32]),
+            })
+            .collect();
+        let mut history = History::default();
+        for trade in &trades {
+            history.push(trade.clone());
+            let mut recorded = vec![Change::Trade(trade.clone())];
+            recorded.extend(
+                history
+                    .buckets(trade)
+                    .into_iter()
+                    .map(|(interval, bucket)| Change::Candle(interval, bucket)),
+            );
+            store.apply(&a, &recorded).unwrap();
+        }
+
+        // Adding a market again keeps its orders and counters
+        store.add_market(&a).unwrap();
+
+        let saved = store.load().unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0].trades, trades);
+        let (restored_pair, mut restored, restored_history) = restore(saved[0].clone());
+        assert_eq!(restored_pair, a);
+        assert_eq!(restored_history.next_id(), 2);
+        for interval in INTERVALS {
+            assert_eq!(
+                restored_history.candles(interval),
+                history.candles(interval)
+            );
+        }
+        assert_eq!(saved[1].pair, b);
+        assert!(saved[1].orders.is_empty());
+        assert!(saved[1].trades.is_empty());
+        assert!(saved[1].candles.is_empty());
+
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored.get(bid).unwrap().quantity, 6);
+        assert_eq!(Counters::of(&restored), Counters::of(&book));
+        assert_eq!(
+            restored.add_ask([2; 4], 100, 1, addresses()),
+            book.next_id()
+        );
+    }
+
+    #[test]
+    fn memory_store_restores_books() {
+        restores_books(&MemoryStore::default());
+    }
+
+    #[test]
+    fn sled_store_restores_books() {
+        restores_books(&SledStore::temporary().unwrap());
+    }
+
+    // Saves more trades and candles than a history keeps, then loads only the latest
+    fn loads_the_latest_trades(store: &dyn Store) {
+        let a = pair(1);
+        store.add_market(&a).unwrap();
+
+        // A trade a minute, so every trade has its own minute candle
+        let count = RECENT_TRADES as u64 + 3;
+        let mut history = History::default();
+        let mut changes = Vec::new();
+        for id in 0..count {
+            let trade = trade(id, id * 60_000);
+            history.push(trade.clone());
+            changes.push(Change::Trade(trade.clone()));
+            changes.extend(
+                history
+                    .buckets(&trade)
+                    .into_iter()
+                    .map(|(interval, bucket)| Change::Candle(interval, bucket)),
+            );
+        }
+        store.apply(&a, &changes).unwrap();
+
+        let saved = store.load().unwrap();
+        assert_eq!(saved[0].trades.len(), RECENT_TRADES);
+        assert_eq!(saved[0].trades[0].id, 3);
+        assert_eq!(saved[0].next_trade, count);
+        let minutes = saved[0]
+            .candles
+            .iter()
+            .filter(|(interval, _)| *interval == Interval::Minute)
+            .count();
+        assert_eq!(minutes, MAX_CANDLES);
+
+        let (_, _, restored) = restore(saved[0].clone());
+        assert_eq!(restored.next_id(), count);
+        assert!(restored.latest().eq(history.latest()));
+        for interval in INTERVALS {
+            assert_eq!(restored.candles(interval), history.candles(interval));
+        }
+    }
+
+    #[test]
+    fn memory_store_loads_the_latest_trades() {
+        loads_the_latest_trades(&MemoryStore::default());
+    }
+
+    #[test]
+    fn sled_store_loads_the_latest_trades() {
+        loads_the_latest_trades(&SledStore::temporary().unwrap());
+    }
+
+    #[actix_web::test]
+    async fn writer_saves_changes_in_order() {
+        let store = Arc::new(SledStore::temporary().unwrap());
+        let a = pair(1);
+        store.add_market(&a).unwrap();
+        let writer = Writer::spawn(store.clone(), a.clone());
+
+        // Changes queued without waiting are saved before a later one is
+        let mut book = OrderBook::new();
+        let cancelled = book.add_bid([1; 4], 100, 10, addresses());
+        writer.send(change(&book, cancelled));
+        book.cancel(cancelled).unwrap();
+        writer.send(change(&book, cancelled));
+        let bid = book.add_bid([1; 4], 99, 3, addresses());
+        writer.save(change(&book, bid)).await.unwrap();
+
+        let saved = store.load().unwrap();
+        assert_eq!(saved[0].orders.len(), 1);
+        assert_eq!(saved[0].orders[0].id, bid);
+        assert_eq!(saved[0].counters, Counters::of(&book));
+    }
+}
@@ -0,0 +1,481 @@
+//! # Trades
+//! Records settled fills as trades and aggregates them into OHLCV candles.
+//!
+//! A market's [`History`] only keeps its latest trades in memory, and adds
+//! each trade to the candles of every interval as it is recorded, so serving
+//! candles never goes through the trades again. Every trade stays in the store,
+//! along with the candle buckets each trade changed, so a restart restores the
+//! candles without going through the trades either.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, VecDeque},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::bail;
+use essential_types::{ContentAddress, Word};
+use matching_engine::{OrderId, Owner};
+use serde::{Deserialize, Serialize};
+
+use crate::matcher::Settled;
+
+/// How many of the latest trades a market keeps in memory.
+pub const RECENT_TRADES: usize = 10_000;
+/// How many of the latest candles of each interval a market keeps.
+pub const MAX_CANDLES: usize = 1_000;
+
+/// Every interval, in the order of [`Interval::index`].
+pub const INTERVALS: [Interval; 3] = [Interval::Minute, Interval::FiveMinutes, Interval::Hour];
+
+/// A settled fill.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trade {
+    /// The position of the trade in its market, starting from 0.
+    pub id: u64,
+    /// When the fill was settled, in milliseconds since the unix epoch.
+    pub timestamp: u64,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The amount of token A traded.
+    pub quantity: Word,
+    /// The bid side.
+    pub bid: TradeSide,
+    /// The ask side.
+    pub ask: TradeSide,
+    /// The address of the settlement solution.
+    pub solution: ContentAddress,
+}
+
+/// One side of a trade.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeSide {
+    /// The order that traded.
+    pub order: OrderId,
+    /// The owner of the order.
+    pub owner: Owner,
+}
+
+/// The width of a candle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interval {
+    /// One minute.
+    Minute,
+    /// Five minutes.
+    FiveMinutes,
+    /// One hour.
+    Hour,
+}
+
+/// The latest trades of a market and its candles.
+#[derive(Default)]
+pub struct History {
+    /// The latest trades, oldest first.
+    recent: VecDeque<Trade>,
+    /// The ID given to the next trade.
+    next_id: u64,
+    /// The candles of each interval.
+    candles: [Candles; 3],
+}
+
+/// The candles of one interval by start time.
+#[derive(Default)]
+struct Candles(BTreeMap<u64, Bucket>);
+
+/// A candle and the trades that opened and closed it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bucket {
+    candle: Candle,
+    /// The timestamp and ID of the first trade.
+    first: (u64, u64),
+    /// The timestamp and ID of the last trade.
+    last: (u64, u64),
+}
+
+/// The trades in one interval.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    /// The start of the interval, in milliseconds since the unix epoch.
+    pub start: u64,
+    /// The price of the first trade.
+    pub open: Word,
+    /// The highest price.
+    pub high: Word,
+    /// The lowest price.
+    pub low: Word,
+    /// The price of the last trade.
+    pub close: Word,
+    /// The amount of token A traded.
+    pub volume: Word,
+    /// The number of trades.
+    pub trades: usize,
+}
+
+impl Trade {
+    /// Records a settled fill as the trade with the ID, settled now.
+    pub fn new(id: u64, settled: &Settled) -> Self {
+        Self {
+            id,
+            timestamp: now(),
+            price: settled.price,
+            quantity: settled.quantity,
+            bid: TradeSide {
+                order: settled.bid.id,
+                owner: settled.bid.owner,
+            },
+            ask: TradeSide {
+                order: settled.ask.id,
+                owner: settled.ask.owner,
+            },
+            solution: settled.solution.clone(),
+        }
+    }
+}
+
+impl History {
+    /// Restores the history from the latest trades and the candle buckets of
+    /// each interval that were saved, and the ID of the next trade.
+    pub fn restore(trades: Vec<Trade>, candles: Vec<(Interval, Bucket)>, next_id: u64) -> Self {
+        let mut history = Self {
+            next_id,
+            ..Self::default()
+        };
+        for (interval, bucket) in candles {
+            history.candles[interval.index()].insert(bucket);
+        }
+        for trade in trades {
+            history.next_id = history.next_id.max(trade.id + 1);
+            history.recent.push_back(trade);
+        }
+        while history.recent.len() > RECENT_TRADES {
+            history.recent.pop_front();
+        }
+        history
+    }
+
+    /// The ID the next trade will be given.
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// Records a trade, dropping the oldest once there are too many.
+    pub fn push(&mut self, trade: Trade) {
+        self.next_id = self.next_id.max(trade.id + 1);
+        for interval in INTERVALS {
+            self.candles[interval.index()].add(&trade, interval);
+        }
+        self.recent.push_back(trade);
+        if self.recent.len() > RECENT_TRADES {
+            self.recent.pop_front();
+        }
+    }
+
+    /// The latest trades, newest first.
+    pub fn latest(&self) -> impl Iterator<Item = &Trade> + '_ {
+        self.recent.iter().rev()
+    }
+
+    /// The latest candles of the interval, oldest first.
+    /// Intervals without trades have no candle.
+    pub fn candles(&self, interval: Interval) -> Vec<Candle> {
+        self.candles[interval.index()].to_vec()
+    }
+
+    /// The bucket of each interval the trade is in, to save once it is pushed.
+    pub fn buckets(&self, trade: &Trade) -> Vec<(Interval, Bucket)> {
+        INTERVALS
+            .into_iter()
+            .filter_map(|interval| {
+                let start = interval.start(trade.timestamp);
+                let bucket = self.candles[interval.index()].0.get(&start)?;
+                Some((interval, bucket.clone()))
+            })
+            .collect()
+    }
+}
+
+impl Bucket {
+    /// The start of the candle's interval.
+    pub fn start(&self) -> u64 {
+        self.candle.start
+    }
+}
+
+impl Candles {
+    /// Adds the trade to the candle of its interval.
+    ///
+    /// Trades may come in any order. The open and close are the trades with
+    /// the earliest and latest timestamp, with the ID breaking ties.
+    fn add(&mut self, trade: &Trade, interval: Interval) {
+        let start = interval.start(trade.timestamp);
+        let at = (trade.timestamp, trade.id);
+        match self.0.entry(start) {
+            Entry::Vacant(entry) => {
+                entry.insert(Bucket {
+                    candle: Candle {
+                        start,
+                        open: trade.price,
+                        high: trade.price,
+                        low: trade.price,
+                        close: trade.price,
+                        volume: trade.quantity,
+                        trades: 1,
+                    },
+                    first: at,
+                    last: at,
+                });
+            }
+            Entry::Occupied(mut entry) => {
+                let bucket = entry.get_mut();
+                let candle = &mut bucket.candle;
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.volume = candle.volume.saturating_add(trade.quantity);
+                candle.trades += 1;
+                if at < bucket.first {
+                    bucket.first = at;
+                    candle.open = trade.price;
+                }
+                if at > bucket.last {
+                    bucket.last = at;
+                    candle.close = trade.price;
+                }
+            }
+        }
+        self.trim();
+    }
+
+    /// Adds a saved bucket, replacing the candle it was saved over.
+    fn insert(&mut self, bucket: Bucket) {
+        self.0.insert(bucket.start(), bucket);
+        self.trim();
+    }
+
+    /// Drops the oldest candles once there are too many.
+    fn trim(&mut self) {
+        while self.0.len() > MAX_CANDLES {
+            self.0.pop_first();
+        }
+    }
+
+    fn to_vec(&self) -> Vec<Candle> {
+        self.0
+            .values()
+            .map(|bucket| bucket.candle.clone())
+            .collect()
+    }
+}
+
+impl Interval {
+    /// The position of the interval in [`INTERVALS`].
+    pub fn index(self) -> usize {
+        match self {
+            Self::Minute => 0,
+            Self::FiveMinutes => 1,
+            Self::Hour => 2,
+        }
+    }
+
+    /// The width of the interval in milliseconds.
+    pub fn millis(self) -> u64 {
+        match self {
+            Self::Minute => 60_000,
+            Self::FiveMinutes => 5 * 60_000,
+            Self::Hour => 60 * 60_000,
+        }
+    }
+
+    /// The start of the interval the timestamp is in.
+    fn start(self, timestamp: u64) -> u64 {
+        timestamp - timestamp % self.millis()
+    }
+}
+
+impl FromStr for Interval {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1m" => Ok(Self::Minute),
+            "5m" => Ok(Self::FiveMinutes),
+            "1h" => Ok(Self::Hour),
+            _ => bail!("Unknown interval {}, expected 1m, 5m or 1h", s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The current time in milliseconds since the unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Aggregates trades, in any order, into candles
+    fn candles(trades: &[Trade], interval: Interval) -> Vec<Candle> {
+        let mut candles = Candles::default();
+        for trade in trades {
+            candles.add(trade, interval);
+        }
+        candles.to_vec()
+    }
+
+    fn trade(id: u64, timestamp: u64, price: Word, quantity: Word) -> Trade {
+        let side = TradeSide {
+            order: 0,
+            owner: [0; 4],
+        };
+        Trade {
+            id,
+            timestamp,
+            price,
+            quantity,
+            bid: side.clone(),
+            ask: side,
+            solution: ContentAddress([0; 32]),
+        }
+    }
+
+    #[test]
+    fn aggregates_candles() {
+        let trades = [
+            trade(0, 60_000, 100, 1),
+            trade(1, 90_000, 104, 2),
+            trade(2, 100_000, 98, 3),
+            trade(3, 119_999, 101, 4),
+            // Nothing traded in the third minute
+            trade(4, 185_000, 110, 5),
+        ];
+        let minutes = candles(&trades, Interval::Minute);
+        assert_eq!(
+            minutes,
+            [
+                Candle {
+                    start: 60_000,
+                    open: 100,
+                    high: 104,
+                    low: 98,
+                    close: 101,
+                    volume: 10,
+                    trades: 4,
+                },
+                Candle {
+                    start: 180_000,
+                    open: 110,
+                    high: 110,
+                    low: 110,
+                    close: 110,
+                    volume: 5,
+                    trades: 1,
+                },
+            ]
+        );
+
+        let five = candles(&trades, Interval::FiveMinutes);
+        assert_eq!(five.len(), 1);
+        assert_eq!(
+            (five[0].open, five[0].close, five[0].volume),
+            (100, 110, 15)
+        );
+        assert!(candles(&[], Interval::Hour).is_empty());
+    }
+
+    #[test]
+    fn aggregates_unsorted_trades() {
+        // A clock step back settles a later trade with an earlier timestamp
+        let trades = [
+            trade(0, 90_000, 104, 2),
+            trade(1, 185_000, 110, 5),
+            trade(2, 60_000, 100, 1),
+            trade(3, 119_999, 101, 4),
+            trade(4, 60_000, 97, 1),
+        ];
+        let minutes = candles(&trades, Interval::Minute);
+        assert_eq!(minutes.len(), 2);
+        assert_eq!(
+            minutes[0],
+            Candle {
+                start: 60_000,
+                open: 100,
+                high: 104,
+                low: 97,
+                close: 101,
+                volume: 8,
+                trades: 4,
+            }
+        );
+        assert_eq!(minutes[1].start, 180_000);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let count = RECENT_TRADES as u64 + 5;
+        let mut history = History::default();
+        for id in 0..count {
+            history.push(trade(id, id * 60_000, 100, 1));
+        }
+        assert_eq!(history.next_id(), count);
+        assert_eq!(history.latest().count(), RECENT_TRADES);
+        assert_eq!(history.latest().next().unwrap().id, count - 1);
+        assert_eq!(history.latest().last().unwrap().id, 5);
+
+        // One trade a minute, so only the latest minute candles are kept
+        let minutes = history.candles(Interval::Minute);
+        assert_eq!(minutes.len(), MAX_CANDLES);
+        assert_eq!(minutes.last().unwrap().start, (count - 1) * 60_000);
+        let hours = history.candles(Interval::Hour);
+        assert_eq!(hours.len() as u64, (count - 1) / 60 + 1);
+        assert_eq!(hours[0].trades, 60);
+
+        // The candles built as trades come match aggregating them at once
+        let trades: Vec<_> = history.latest().cloned().collect();
+        assert_eq!(
+            history.candles(Interval::Hour)[1..],
+            candles(&trades, Interval::Hour)[1..]
+        );
+    }
+
+    #[test]
+    fn restores_saved_candles() {
+        let trades = [
+            trade(0, 60_000, 100, 1),
+            trade(1, 90_000, 104, 2),
+            trade(2, 185_000, 110, 5),
+        ];
+        let mut history = History::default();
+        let mut saved = Vec::new();
+        for trade in &trades {
+            history.push(trade.clone());
+            saved.extend(history.buckets(trade));
+        }
+
+        // Each save of a bucket replaces the one before, so the candles come
+        // back whole even though only the latest trades are restored
+        let restored = History::restore(trades[1..].to_vec(), saved, 3);
+        for interval in INTERVALS {
+            assert_eq!(restored.candles(interval), history.candles(interval));
+        }
+        assert_eq!(restored.next_id(), 3);
+        assert_eq!(restored.latest().count(), 2);
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!("1m".parse::<Interval>().unwrap(), Interval::Minute);
+        assert_eq!("5m".parse::<Interval>().unwrap(), Interval::FiveMinutes);
+        assert_eq!("1h".parse::<Interval>().unwrap(), Interval::Hour);
+        assert!("1d".parse::<Interval>().is_err());
+    }
+}
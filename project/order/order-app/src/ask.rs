@@ -0,0 +1,84 @@
+//! # Ask
+//! Contains functionality for filling an ask in the order contract.
+
+use essential_types::{solution::Solution, PredicateAddress, Word};
+
+use crate::{book::Side, fill, Query};
+
+/// Represents the initial data required for filling an ask.
+pub struct Init {
+    /// The address of the order instance's `Ask` predicate.
+    pub predicate: PredicateAddress,
+    /// The amount of token A being sold.
+    pub amount: Word,
+    /// The price the fill trades at, no less than the ask's price.
+    pub fill_price: Word,
+    /// The current remaining amount of the ask, stored in `bid_amount`.
+    pub bid_amount: Query,
+    /// The current price of the order instance.
+    pub price: Query,
+    /// The current nonce of the order instance.
+    pub nonce: Query,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+}
+
+/// Contains all necessary information to build an ask solution.
+pub struct BuildSolution {
+    /// The address of the order instance's `Ask` predicate.
+    pub predicate: PredicateAddress,
+    /// The amount of token A being sold.
+    pub amount: Word,
+    /// The price the fill trades at.
+    pub fill_price: Word,
+    /// The remaining amount of the ask after this fill.
+    pub new_ask_amount: Word,
+}
+
+/// Prepares an ask fill from the current state of the order instance.
+pub fn init(init: Init) -> anyhow::Result<BuildSolution> {
+    let Init {
+        predicate,
+        amount,
+        fill_price,
+        bid_amount: current,
+        price,
+        nonce,
+        instance_nonce,
+    } = init;
+    let new_ask_amount = fill::remaining(
+        Side::Ask,
+        amount,
+        fill_price,
+        current,
+        price,
+        nonce,
+        instance_nonce,
+    )?;
+    Ok(BuildSolution {
+        predicate,
+        amount,
+        fill_price,
+        new_ask_amount,
+    })
+}
+
+/// Builds an ask solution based on the provided data.
+///
+/// The token balance changes the `Ask` predicate constrains must be
+/// solved by token transfers in the same solution.
+pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
+    let BuildSolution {
+        predicate,
+        amount,
+        fill_price,
+        new_ask_amount,
+    } = build;
+    Ok(fill::solution(
+        Side::Ask,
+        predicate,
+        amount,
+        fill_price,
+        new_ask_amount,
+    ))
+}
@@ -0,0 +1,84 @@
+//! # Bid
+//! Contains functionality for filling a bid in the order contract.
+
+use essential_types::{solution::Solution, PredicateAddress, Word};
+
+use crate::{book::Side, fill, Query};
+
+/// Represents the initial data required for filling a bid.
+pub struct Init {
+    /// The address of the order instance's `Bid` predicate.
+    pub predicate: PredicateAddress,
+    /// The amount of token A being bought.
+    pub amount: Word,
+    /// The price the fill trades at, no more than the bid's price.
+    pub fill_price: Word,
+    /// The current remaining amount of the bid.
+    pub bid_amount: Query,
+    /// The current price of the order instance.
+    pub price: Query,
+    /// The current nonce of the order instance.
+    pub nonce: Query,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+}
+
+/// Contains all necessary information to build a bid solution.
+pub struct BuildSolution {
+    /// The address of the order instance's `Bid` predicate.
+    pub predicate: PredicateAddress,
+    /// The amount of token A being bought.
+    pub amount: Word,
+    /// The price the fill trades at.
+    pub fill_price: Word,
+    /// The remaining amount of the bid after this fill.
+    pub new_bid_amount: Word,
+}
+
+/// Prepares a bid fill from the current state of the order instance.
+pub fn init(init: Init) -> anyhow::Result<BuildSolution> {
+    let Init {
+        predicate,
+        amount,
+        fill_price,
+        bid_amount: current,
+        price,
+        nonce,
+        instance_nonce,
+    } = init;
+    let new_bid_amount = fill::remaining(
+        Side::Bid,
+        amount,
+        fill_price,
+        current,
+        price,
+        nonce,
+        instance_nonce,
+    )?;
+    Ok(BuildSolution {
+        predicate,
+        amount,
+        fill_price,
+        new_bid_amount,
+    })
+}
+
+/// Builds a bid solution based on the provided data.
+///
+/// The token balance changes the `Bid` predicate constrains must be
+/// solved by token transfers in the same solution.
+pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
+    let BuildSolution {
+        predicate,
+        amount,
+        fill_price,
+        new_bid_amount,
+    } = build;
+    Ok(fill::solution(
+        Side::Bid,
+        predicate,
+        amount,
+        fill_price,
+        new_bid_amount,
+    ))
+}
@@ -0,0 +1,193 @@
+//! # Book
+//! Contains the signed messages sent to the order book backend.
+//!
+//! The owner signs the words of each message the same way the token contract
+//! expects for `@verify_key`, so the backend can check the signature by
+//! recovering the key and comparing its hash to the owner.
+//!
+//! Every message names the market's pair, so a signature for one market can
+//! never be replayed on another.
+
+use essential_types::{
+    convert::word_4_from_u8_32, ContentAddress, PredicateAddress, Signature, Word,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::deploy::Pair;
+
+/// Tags the words of a bid so they can never be replayed as another message.
+const BID: Word = 0;
+/// Tags the words of an ask.
+const ASK: Word = 1;
+/// Tags the words of a cancel.
+const CANCEL: Word = 2;
+/// Tags the words of a removal of every order at a price.
+const REMOVE: Word = 3;
+
+/// Which side of the book an order is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    /// Buys token A.
+    Bid,
+    /// Sells token A.
+    Ask,
+}
+
+/// An order to add to the book.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewOrder {
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The amount of token A to buy or sell.
+    pub quantity: Word,
+    /// The `Bid` or `Ask` predicate followed by the `UpdateVars` predicate
+    /// of the order contract instance.
+    pub addresses: Vec<PredicateAddress>,
+    /// The owner's signature over [`NewOrder::to_words`].
+    pub signature: Signature,
+}
+
+/// A request to cancel an order, or every order at a price, in the book.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelOrder {
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The owner's signature over [`cancel_words`] or [`remove_words`].
+    pub signature: Signature,
+}
+
+impl NewOrder {
+    /// The words the owner signs for an order on the side in the pair's market.
+    pub fn to_words(&self, side: Side, pair: &Pair) -> Vec<Word> {
+        order_words(
+            side,
+            pair,
+            self.owner,
+            self.price,
+            self.quantity,
+            &self.addresses,
+        )
+    }
+}
+
+/// The words the owner signs for an order in the pair's market.
+pub fn order_words(
+    side: Side,
+    pair: &Pair,
+    owner: [Word; 4],
+    price: Word,
+    quantity: Word,
+    addresses: &[PredicateAddress],
+) -> Vec<Word> {
+    let tag = match side {
+        Side::Bid => BID,
+        Side::Ask => ASK,
+    };
+    let mut words = vec![tag];
+    words.extend(pair_words(pair));
+    words.extend(owner);
+    words.push(price);
+    words.push(quantity);
+    words.push(addresses.len() as Word);
+    for address in addresses {
+        words.extend(word_4_from_u8_32(address.contract.0));
+        words.extend(word_4_from_u8_32(address.predicate.0));
+    }
+    words
+}
+
+/// The words the owner signs to cancel the order with the ID in the pair's market.
+///
+/// The contract is the order's contract instance, so the signature only
+/// cancels that order even if a book reuses the ID.
+pub fn cancel_words(pair: &Pair, id: u64, contract: &ContentAddress) -> Vec<Word> {
+    let mut words = vec![CANCEL];
+    words.extend(pair_words(pair));
+    words.push(id as Word);
+    words.extend(word_4_from_u8_32(contract.0));
+    words
+}
+
+/// The words the owner signs to remove all of their orders on the side at the price.
+///
+/// The IDs are those of every order the owner has at the price, in the order
+/// they were added. Once those orders are gone the signature matches nothing,
+/// so it can not be replayed against orders placed later.
+pub fn remove_words(pair: &Pair, side: Side, price: Word, ids: &[u64]) -> Vec<Word> {
+    let side = match side {
+        Side::Bid => BID,
+        Side::Ask => ASK,
+    };
+    let mut words = vec![REMOVE];
+    words.extend(pair_words(pair));
+    words.extend([side, price, ids.len() as Word]);
+    words.extend(ids.iter().map(|id| *id as Word));
+    words
+}
+
+/// The words of token A then token B.
+fn pair_words(pair: &Pair) -> impl Iterator<Item = Word> {
+    word_4_from_u8_32(pair.token_a.0)
+        .into_iter()
+        .chain(word_4_from_u8_32(pair.token_b.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(a: u8, b: u8) -> Pair {
+        Pair::new(ContentAddress([a; 32]), ContentAddress([b; 32])).unwrap()
+    }
+
+    #[test]
+    fn words_differ_by_side_and_fields() {
+        let pair = pair(3, 4);
+        let addresses = [PredicateAddress {
+            contract: ContentAddress([1; 32]),
+            predicate: ContentAddress([2; 32]),
+        }];
+        let bid = order_words(Side::Bid, &pair, [1; 4], 100, 10, &addresses);
+        assert_eq!(bid.len(), 1 + 8 + 4 + 3 + 8);
+        let order = |side, price, addresses: &[PredicateAddress]| {
+            order_words(side, &pair, [1; 4], price, 10, addresses)
+        };
+        assert_ne!(bid, order(Side::Ask, 100, &addresses));
+        assert_ne!(bid, order(Side::Bid, 101, &addresses));
+        assert_ne!(bid, order(Side::Bid, 100, &[]));
+        let contract = ContentAddress([1; 32]);
+        assert_ne!(cancel_words(&pair, 0, &contract)[..], bid[..10]);
+        assert_ne!(
+            remove_words(&pair, Side::Bid, 100, &[0]),
+            remove_words(&pair, Side::Ask, 100, &[0])
+        );
+        assert_ne!(
+            remove_words(&pair, Side::Bid, 100, &[0]),
+            remove_words(&pair, Side::Bid, 100, &[0, 1])
+        );
+    }
+
+    #[test]
+    fn words_differ_by_market() {
+        let (market, other) = (pair(3, 4), pair(4, 3));
+        let contract = ContentAddress([1; 32]);
+        assert_ne!(
+            order_words(Side::Bid, &market, [1; 4], 100, 10, &[]),
+            order_words(Side::Bid, &other, [1; 4], 100, 10, &[])
+        );
+        assert_ne!(
+            cancel_words(&market, 0, &contract),
+            cancel_words(&other, 0, &contract)
+        );
+        assert_ne!(
+            cancel_words(&market, 0, &contract),
+            cancel_words(&market, 0, &ContentAddress([2; 32]))
+        );
+        assert_ne!(
+            remove_words(&market, Side::Bid, 100, &[0]),
+            remove_words(&other, Side::Bid, 100, &[0])
+        );
+    }
+}
@@ -1,77 +1,464 @@
-use std::process::Command;
-use std::fs;
-use std::io::{self, Write};
-
-fn deploy_pint_contract() {
-    // Running the "pint build" command
-    let output = Command::new("pint")
-        .arg("build")
-        .output()
-        .expect("Failed to execute pint build");
-
-    if output.status.success() {
-        // If the command was successful
-        println!("Contract built successfully.");
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        parse_contract_ids(&stdout);
-    } else {
-        // If the command failed
-        eprintln!("Failed to build contract.");
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        eprintln!("Error: {}", stderr);
+//! # Deploy
+//! Compiles a unique instance of the order contract.
+//!
+//! Every order is its own contract instance. The instance is made unique by
+//! rendering `unique.pnt` with the trading pair and a nonce, then
+//! compiling the project in its own temporary directory so parallel
+//! deployments never share files.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, ensure, Context};
+use essential_app_utils::compile::compile_pint_project;
+use essential_rest_client::node_client::EssentialNodeClient;
+use essential_types::{contract::Contract, ContentAddress, PredicateAddress, Word};
+use serde::{Deserialize, Serialize};
+
+/// The order contract's manifest.
+const MANIFEST: &str = include_str!("../../pint.toml");
+/// The order contract's predicates.
+const CONTRACT: &str = include_str!("../../src/contract.pnt");
+/// The macros used by the order contract.
+const MACROS: &str = include_str!("../../src/macros.pnt");
+
+/// The tokens an order trades.
+///
+/// Orders buy or sell token A, priced in token B.
+/// Displays as its market ID, the hex of token A and token B joined by `-`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Pair {
+    /// The contract address of token A.
+    pub token_a: ContentAddress,
+    /// The contract address of token B.
+    pub token_b: ContentAddress,
+}
+
+/// The constants that make an order contract instance unique.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    /// The tokens the instance trades.
+    pub pair: Pair,
+    /// The unique nonce of the instance.
+    pub nonce: Word,
+}
+
+/// The addresses of a compiled order contract instance.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    /// The address of the order contract instance.
+    pub contract: ContentAddress,
+    /// The address of the `Bid` predicate.
+    pub bid: PredicateAddress,
+    /// The address of the `Ask` predicate.
+    pub ask: PredicateAddress,
+    /// The address of the `UpdateVars` predicate.
+    pub update: PredicateAddress,
+    /// The address of the `Place` predicate.
+    pub place: PredicateAddress,
+    /// The unique nonce of the instance.
+    pub nonce: Word,
+    /// The tokens the instance trades.
+    pub pair: Pair,
+}
+
+/// A problem reported by the pint compiler.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How severe the problem is.
+    pub severity: Severity,
+    /// The compiler's message.
+    pub message: String,
+    /// Where in the source the problem is, if known.
+    pub location: Option<Location>,
+}
+
+/// The severity of a diagnostic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The contract failed to compile.
+    Error,
+    /// The contract compiled with a warning.
+    Warning,
+}
+
+/// A position in a pint source file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// The source file.
+    pub file: PathBuf,
+    /// The line, starting from 1.
+    pub line: usize,
+    /// The column, starting from 1.
+    pub column: usize,
+}
+
+/// Errors from building the order contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// The contract failed to compile.
+    Diagnostics(Vec<Diagnostic>),
+}
+
+/// A compiled order contract instance.
+pub struct Compiled {
+    /// The compiled contract, ready to be deployed.
+    pub contract: Contract,
+    /// The addresses of the contract and its predicates.
+    pub deployment: Deployment,
+}
+
+impl Pair {
+    /// Creates a pair trading token A against token B.
+    ///
+    /// Fails if both are the same token.
+    pub fn new(token_a: ContentAddress, token_b: ContentAddress) -> anyhow::Result<Self> {
+        ensure!(
+            token_a != token_b,
+            "Token A and token B must be different contracts, both are {}",
+            token_a
+        );
+        Ok(Self { token_a, token_b })
+    }
+
+    /// Checks that both tokens are deployed and minted.
+    pub async fn check_deployed(&self, node: &EssentialNodeClient) -> anyhow::Result<()> {
+        for (name, token) in [("A", &self.token_a), ("B", &self.token_b)] {
+            let decimals = node
+                .query_state(token.clone(), token::decimals_key())
+                .await?;
+            ensure!(
+                decimals.is_some_and(|d| !d.is_empty()),
+                "Token {} {} is not deployed",
+                name,
+                token
+            );
+        }
+        Ok(())
     }
 }
 
-fn overwrite_last_n_chars(file_path: &str, overwrite_str: &str, n: usize) -> io::Result<()> {
-    // Step 1: Read the entire content of the file
-    let mut content = fs::read_to_string(file_path)?;
+impl Instance {
+    /// Creates an instance trading the pair with a fresh nonce.
+    pub fn new(pair: Pair) -> Self {
+        Self {
+            pair,
+            nonce: unique_nonce(),
+        }
+    }
 
-    // Step 2: Check if the content is long enough and overwrite the last n characters
-    if content.len() >= n {
-        // Truncate the content to remove the last n characters
-        content.truncate(content.len() - n);
-        // Append the overwrite string
-        content.push_str(overwrite_str);
-    } else {
-        println!("The file has less than {} characters.", n);
-        return Ok(()); // Exit early if the file is too short
+    /// Renders the `unique.pnt` module for this instance.
+    pub fn render_unique(&self) -> String {
+        format!(
+            "const contract_A = {};\nconst contract_B = {};\n\nconst nonce = {};\n",
+            b256(&self.pair.token_a),
+            b256(&self.pair.token_b),
+            self.nonce
+        )
     }
+}
 
-    // Step 3: Write the modified content back to the file
-    let mut file = fs::File::create(file_path)?;
-    file.write_all(content.as_bytes())?;
+/// Compiles the order contract for the instance.
+///
+/// This requires `pint` be available on PATH.
+/// Fails with a [`BuildError::Diagnostics`] if the compiler reported any.
+pub async fn compile(instance: &Instance) -> anyhow::Result<Compiled> {
+    let dir = tempfile::Builder::new()
+        .prefix(&format!("order-{}-", instance.nonce))
+        .tempdir()?;
+    write_project(dir.path(), instance)?;
+    let contract = match compile_pint_project(dir.path().to_path_buf()).await {
+        Ok(contract) => contract,
+        Err(err) => {
+            // The error carries what the compiler printed.
+            let diagnostics = parse_diagnostics(&format!("{:#}", err));
+            if diagnostics.is_empty() {
+                return Err(err.context("Failed to compile the order contract"));
+            }
+            return Err(BuildError::Diagnostics(diagnostics).into());
+        }
+    };
+    let names = predicate_names(&dir.path().join("out/debug/order-abi.json"))?;
+    let deployment = deployment(&contract, &names, instance)?;
+    Ok(Compiled {
+        contract,
+        deployment,
+    })
+}
+
+/// Returns a nonce that is unique to this call.
+pub fn unique_nonce() -> Word {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let pid = u64::from(std::process::id()) << 40;
+    ((now.wrapping_add(count) ^ pid) & i64::MAX as u64) as Word
+}
 
+/// Writes the order project for the instance into `dir`.
+fn write_project(dir: &Path, instance: &Instance) -> anyhow::Result<()> {
+    let src = dir.join("src");
+    std::fs::create_dir_all(&src)?;
+    std::fs::write(dir.join("pint.toml"), MANIFEST)?;
+    std::fs::write(src.join("contract.pnt"), CONTRACT)?;
+    std::fs::write(src.join("macros.pnt"), MACROS)?;
+    std::fs::write(src.join("unique.pnt"), instance.render_unique())?;
     Ok(())
 }
 
-fn parse_contract_ids(output: &str) {
-    // Split the output by lines
-    let lines: Vec<&str> = output.lines().collect();
+/// Reads the predicate names from the ABI, in the same order as the contract's predicates.
+fn predicate_names(abi: &Path) -> anyhow::Result<Vec<String>> {
+    let abi: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(abi).with_context(|| format!("Missing ABI {}", abi.display()))?,
+    )?;
+    abi["predicates"]
+        .as_array()
+        .context("ABI has no predicates")?
+        .iter()
+        .map(|p| {
+            let name = p["name"].as_str().context("Predicate has no name")?;
+            Ok(name.trim_start_matches("::").to_string())
+        })
+        .collect()
+}
+
+/// Maps the compiled predicates to their addresses by name.
+fn deployment(
+    contract: &Contract,
+    names: &[String],
+    instance: &Instance,
+) -> anyhow::Result<Deployment> {
+    ensure!(
+        names.len() == contract.predicates.len(),
+        "ABI has {} predicates but the contract has {}",
+        names.len(),
+        contract.predicates.len()
+    );
+    let address = essential_hash::contract_addr::from_contract(contract);
+    let find = |name: &str| -> anyhow::Result<PredicateAddress> {
+        let ix = names
+            .iter()
+            .position(|n| n == name)
+            .with_context(|| format!("Contract has no predicate {}", name))?;
+        Ok(PredicateAddress {
+            contract: address.clone(),
+            predicate: essential_hash::content_addr(&contract.predicates[ix]),
+        })
+    };
+    Ok(Deployment {
+        bid: find("Bid")?,
+        ask: find("Ask")?,
+        update: find("UpdateVars")?,
+        place: find("Place")?,
+        contract: address,
+        nonce: instance.nonce,
+        pair: instance.pair.clone(),
+    })
+}
 
-    for line in lines {
-        // Find lines that start with "contract" or contain "::"
-        if line.contains("::") || line.contains("      ") {
-            // Split the line by whitespace and extract the last part (the contract ID)
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if let Some(contract_id) = parts.last() {
-                println!("Found contract ID: {}", contract_id);
+/// Parses the diagnostics that `pint build` prints on failure.
+///
+/// Each diagnostic starts with an `Error:` or `Warning:` line and may be
+/// followed by a `╭─[file:line:column]` location. Other lines are skipped,
+/// so the output may hold anything else the build printed around them.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        let severity = if let Some(message) = line.strip_prefix("Error:") {
+            Some((Severity::Error, message))
+        } else {
+            line.strip_prefix("Warning:")
+                .map(|message| (Severity::Warning, message))
+        };
+        if let Some((severity, message)) = severity {
+            diagnostics.push(Diagnostic {
+                severity,
+                message: message.trim().to_string(),
+                location: None,
+            });
+        } else if let Some(location) = line
+            .strip_prefix("╭─[")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            if let Some(last) = diagnostics.last_mut().filter(|d| d.location.is_none()) {
+                last.location = parse_location(location);
             }
         }
     }
+    diagnostics
 }
 
-fn main() -> io::Result<()> {
-    // Assume user input triggers the function
-    let file_path = "/Users/jeffreyklinck/EthSF-1/project/order/src/unique.pnt"; // specify the target file
-    let overwrite_str = "10;"; //Generate from a number
+/// Parses a `file:line:column` location.
+fn parse_location(location: &str) -> Option<Location> {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = PathBuf::from(parts.next()?);
+    Some(Location { file, line, column })
+}
 
-    let n = 3;
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            hex::encode_upper(self.token_a.0),
+            hex::encode_upper(self.token_b.0)
+        )
+    }
+}
 
-    overwrite_last_n_chars(file_path, overwrite_str, n)?;
+impl FromStr for Pair {
+    type Err = anyhow::Error;
 
-    deploy_pint_contract();
+    /// Parses a market ID as written by `Display`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((token_a, token_b)) = s.split_once('-') else {
+            bail!("Expected <token A>-<token B>, got: {}", s)
+        };
+        let token = |hex_str: &str| -> anyhow::Result<ContentAddress> {
+            let Ok(address) = <[u8; 32]>::try_from(hex::decode(hex_str)?) else {
+                bail!("Token address must be 32 bytes: {}", hex_str)
+            };
+            Ok(ContentAddress(address))
+        };
+        Self::new(token(token_a)?, token(token_b)?)
+    }
+}
 
-    Ok(())
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Diagnostics(diagnostics) => {
+                write!(f, "pint build failed")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.location {
+            Some(Location { file, line, column }) => write!(
+                f,
+                "{}:{}:{}: {}: {}",
+                file.display(),
+                line,
+                column,
+                severity,
+                self.message
+            ),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Formats an address as a pint `b256` literal.
+fn b256(address: &ContentAddress) -> String {
+    format!("0x{}", hex::encode_upper(address.0))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn renders_unique_constants() {
+        let instance = Instance {
+            pair: Pair::new(ContentAddress([0xAB; 32]), ContentAddress([0x01; 32])).unwrap(),
+            nonce: 42,
+        };
+        let unique = instance.render_unique();
+        assert!(unique.contains(&format!("const contract_A = 0x{};", "AB".repeat(32))));
+        assert!(unique.contains(&format!("const contract_B = 0x{};", "01".repeat(32))));
+        assert!(unique.contains("const nonce = 42;"));
+    }
+
+    #[test]
+    fn parses_diagnostics() {
+        // As `pint build` reports a redeclared variable, with the lines the
+        // build printed before it.
+        let output = "\
+   Compiling order [contract] (/tmp/order-42-x1)
+Error: symbol `amount` has already been declared
+    ╭─[/tmp/order-42-x1/src/contract.pnt:76:5]
+    │
+ 75 │     var amount: int;
+    ·         ───┬──
+    ·            ╰──── previous declaration of the value `amount` here
+ 76 │     var amount: int;
+    ·         ───┬──
+    ·            ╰──── `amount` redeclared here
+────╯
+Error: could not compile `order` due to previous error
+";
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(
+            diagnostics,
+            [
+                Diagnostic {
+                    severity: Severity::Error,
+                    message: "symbol `amount` has already been declared".to_string(),
+                    location: Some(Location {
+                        file: PathBuf::from("/tmp/order-42-x1/src/contract.pnt"),
+                        line: 76,
+                        column: 5,
+                    }),
+                },
+                Diagnostic {
+                    severity: Severity::Error,
+                    message: "could not compile `order` due to previous error".to_string(),
+                    location: None,
+                },
+            ]
+        );
+        let err = BuildError::Diagnostics(diagnostics).to_string();
+        assert!(err.contains("/tmp/order-42-x1/src/contract.pnt:76:5: error: symbol `amount`"));
+
+        // An error wrapping the output still yields its diagnostics
+        let wrapped = anyhow::anyhow!("{}", output).context("pint build failed");
+        assert_eq!(parse_diagnostics(&format!("{:#}", wrapped)).len(), 2);
+    }
+
+    #[test]
+    fn pair_rejects_same_token() {
+        let token = ContentAddress([0xAB; 32]);
+        assert!(Pair::new(token.clone(), token).is_err());
+    }
+
+    #[test]
+    fn pair_ids_round_trip() {
+        let pair = Pair::new(ContentAddress([0xAB; 32]), ContentAddress([0x01; 32])).unwrap();
+        let id = pair.to_string();
+        assert_eq!(id, format!("{}-{}", "AB".repeat(32), "01".repeat(32)));
+        assert_eq!(id.parse::<Pair>().unwrap(), pair);
+        assert_eq!(id.to_lowercase().parse::<Pair>().unwrap(), pair);
+        assert!("AB".repeat(32).parse::<Pair>().is_err());
+        assert!(format!("AB-{}", "01".repeat(32)).parse::<Pair>().is_err());
+    }
+
+    #[test]
+    fn nonces_are_unique_and_positive() {
+        let nonces: std::collections::HashSet<_> = (0..1000).map(|_| unique_nonce()).collect();
+        assert_eq!(nonces.len(), 1000);
+        assert!(nonces.iter().all(|n| *n >= 0));
+    }
+}
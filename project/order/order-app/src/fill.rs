@@ -0,0 +1,68 @@
+//! # Fill
+//! Contains the fill logic shared by the `Bid` and `Ask` predicates.
+
+use anyhow::ensure;
+use essential_types::{
+    solution::{Solution, SolutionData},
+    PredicateAddress, Word,
+};
+
+use crate::{bid_amount, book::Side, nonce as instance, price as order_price, Query};
+
+/// Checks a fill of an order on `side` against the current state of the
+/// order instance and returns the order's remaining amount after it.
+///
+/// A bid fills at no more than its price and an ask at no less.
+pub(crate) fn remaining(
+    side: Side,
+    amount: Word,
+    fill_price: Word,
+    current: Query,
+    price: Query,
+    nonce: Query,
+    instance_nonce: Word,
+) -> anyhow::Result<Word> {
+    ensure!(amount > 0, "Amount must be greater than zero");
+    ensure!(fill_price > 0, "Fill price must be greater than zero");
+    ensure!(
+        instance(nonce)? == instance_nonce,
+        "Order has not been placed"
+    );
+    let price = order_price(price)?;
+    match side {
+        Side::Bid => ensure!(fill_price <= price, "Fill price is above the bid's price"),
+        Side::Ask => ensure!(fill_price >= price, "Fill price is below the ask's price"),
+    }
+    bid_amount(current)?
+        .checked_sub(amount)
+        .filter(|left| *left >= 0)
+        .ok_or(match side {
+            Side::Bid => anyhow::anyhow!("Insufficient bid amount"),
+            Side::Ask => anyhow::anyhow!("Insufficient ask amount"),
+        })
+}
+
+/// Builds the solution filling the `side` predicate of an order instance,
+/// leaving `new_amount` of the order.
+pub(crate) fn solution(
+    side: Side,
+    predicate: PredicateAddress,
+    amount: Word,
+    fill_price: Word,
+    new_amount: Word,
+) -> Solution {
+    let decision_variables = match side {
+        Side::Bid => super::order::Bid::Vars { amount, fill_price }.into(),
+        Side::Ask => super::order::Ask::Vars { amount, fill_price }.into(),
+    };
+    let mutations = super::order::storage::mutations().bid_amount(new_amount);
+    let solution = SolutionData {
+        predicate_to_solve: predicate,
+        decision_variables,
+        transient_data: Default::default(),
+        state_mutations: mutations.into(),
+    };
+    Solution {
+        data: vec![solution],
+    }
+}
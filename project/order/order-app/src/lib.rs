@@ -1,5 +1,113 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+#![deny(missing_docs)]
+//! # Order
+//! Order contract front end implementation
+
+use anyhow::bail;
+use essential_types::{solution::Mutation, Key, Value, Word};
+
+/// Module containing the order contract ABI.
+#[allow(missing_docs)]
+pub mod order {
+    pint_abi::gen_from_file! {
+        abi: "../out/debug/order-abi.json",
+        contract:  "../out/debug/order.json",
+    }
+}
+
+pub mod ask;
+pub mod bid;
+pub mod book;
+pub mod deploy;
+mod fill;
+pub mod place;
+pub mod update;
+
+/// Represents a query result, which may or may not contain a value.
+pub struct Query(pub Option<Value>);
+
+/// Generates the key for querying an order's remaining amount.
+pub fn bid_amount_key() -> Key {
+    first_key(order::storage::mutations().bid_amount(0).into())
+}
+
+/// Generates the key for querying an order's price.
+pub fn price_key() -> Key {
+    first_key(order::storage::mutations().price(0).into())
+}
+
+/// Generates the key for querying an order's owner.
+pub fn owner_key() -> Key {
+    first_key(order::storage::mutations().owner([0; 4]).into())
+}
+
+/// Generates the key for querying an order's nonce.
+pub fn nonce_key() -> Key {
+    first_key(order::storage::mutations().nonce(0).into())
+}
+
+/// Generates the key for querying how many times an order has been updated.
+pub fn updates_key() -> Key {
+    first_key(order::storage::mutations().updates(0).into())
+}
+
+/// Extracts the remaining amount from a Query result.
+pub fn bid_amount(bid_amount: Query) -> anyhow::Result<Word> {
+    word(bid_amount)
+}
+
+/// Extracts the price from a Query result.
+pub fn price(price: Query) -> anyhow::Result<Word> {
+    word(price)
+}
+
+/// Extracts the nonce from a Query result.
+pub fn nonce(nonce: Query) -> anyhow::Result<Word> {
+    word(nonce)
+}
+
+/// Extracts the update count from a Query result.
+pub fn updates(updates: Query) -> anyhow::Result<Word> {
+    word(updates)
+}
+
+/// Extracts the owner from a Query result.
+pub fn owner(owner: Query) -> anyhow::Result<Option<[Word; 4]>> {
+    let r = match owner.0 {
+        Some(owner) => match &owner[..] {
+            [] => None,
+            [a, b, c, d] => Some([*a, *b, *c, *d]),
+            _ => bail!("Expected four words, got: {:?}", owner),
+        },
+        None => None,
+    };
+    Ok(r)
+}
+
+/// Returns true if the Query result is nil.
+pub fn is_nil(query: &Query) -> bool {
+    query.0.as_ref().map_or(true, |v| v.is_empty())
+}
+
+/// Extracts a single word from a Query result.
+fn word(query: Query) -> anyhow::Result<Word> {
+    let r = match query.0 {
+        Some(value) => match &value[..] {
+            [] => 0,
+            [value] => *value,
+            _ => bail!("Expected single word, got: {:?}", value),
+        },
+        None => 0,
+    };
+    Ok(r)
+}
+
+/// Returns the key of the first mutation.
+fn first_key(mutations: Vec<Mutation>) -> Key {
+    mutations
+        .into_iter()
+        .next()
+        .expect("Must be a mutation")
+        .key
 }
 
 #[cfg(test)]
@@ -7,8 +115,17 @@ mod tests {
     use super::*;
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn query_words() {
+        assert_eq!(bid_amount(Query(None)).unwrap(), 0);
+        assert_eq!(price(Query(Some(vec![]))).unwrap(), 0);
+        assert_eq!(nonce(Query(Some(vec![7]))).unwrap(), 7);
+        assert!(price(Query(Some(vec![1, 2]))).is_err());
+        assert_eq!(
+            owner(Query(Some(vec![1, 2, 3, 4]))).unwrap(),
+            Some([1, 2, 3, 4])
+        );
+        assert_eq!(owner(Query(None)).unwrap(), None);
+        assert!(is_nil(&Query(Some(vec![]))));
+        assert!(!is_nil(&Query(Some(vec![0]))));
     }
 }
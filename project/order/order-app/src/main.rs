@@ -0,0 +1,348 @@
+//! Command-line interface for placing and managing orders in the order contract.
+
+use clap::{Args, Parser, Subcommand};
+use essential_rest_client::{
+    builder_client::EssentialBuilderClient, node_client::EssentialNodeClient,
+};
+use essential_types::{ContentAddress, Key, Word};
+use order_app::{
+    book::{NewOrder, Side},
+    deploy::{self, Deployment, Instance, Pair},
+    Query,
+};
+use std::path::{Path, PathBuf};
+use token::signer::{Signer, SignerKind};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// Essential wallet directory.
+    /// If not set then a sensible default will be used (like ~/.essential-wallet).
+    #[arg(short, long)]
+    wallet: Option<PathBuf>,
+    /// Where the order owner's signing key is held.
+    /// One of `wallet`, `key` (prompts for a hex encoded secret key)
+    /// or, on unix, `remote:<socket path>` for a signing service.
+    #[arg(short, long, default_value = "wallet")]
+    signer: SignerKind,
+    /// The wallet account of the order owner.
+    /// Only used by, and required for, the `wallet` signer.
+    #[arg(short, long, global = true)]
+    account: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Place {
+    /// The amount of token A to buy or sell.
+    amount: Word,
+    /// The price of token A in token B.
+    price: Word,
+    /// The contract address of token A.
+    /// Encoded as hex.
+    token_a: String,
+    /// The contract address of token B.
+    /// Encoded as hex.
+    token_b: String,
+    /// The address of the node to connect to.
+    node_api: String,
+    /// The address of the builder to connect to.
+    builder_api: String,
+    /// The address of the order book backend to register the order with.
+    backend_api: String,
+    /// Where to write the deployment of the new order contract instance.
+    deployment: PathBuf,
+}
+
+#[derive(Args)]
+struct Update {
+    /// The new amount of token A to buy or sell.
+    amount: Word,
+    /// The new price of token A in token B.
+    price: Word,
+    /// The deployment written when the order was placed.
+    deployment: PathBuf,
+    /// The address of the node to connect to.
+    node_api: String,
+    /// The address of the builder to connect to.
+    builder_api: String,
+}
+
+#[derive(Args)]
+struct Cancel {
+    /// The deployment written when the order was placed.
+    deployment: PathBuf,
+    /// The address of the node to connect to.
+    node_api: String,
+    /// The address of the builder to connect to.
+    builder_api: String,
+}
+
+#[derive(Args)]
+struct Show {
+    /// The deployment written when the order was placed.
+    deployment: PathBuf,
+    /// The address of the node to connect to.
+    node_api: String,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    PlaceBid(Place),
+    PlaceAsk(Place),
+    Update(Update),
+    Cancel(Cancel),
+    Show(Show),
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Cli::parse();
+    if let Err(err) = run(args).await {
+        eprintln!("Command failed because: {}", err);
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let Cli {
+        wallet,
+        signer,
+        account,
+        command,
+    } = cli;
+    match command {
+        Command::PlaceBid(args) => {
+            println!(
+                "placing bid for {} at price {}",
+                args.amount, args.price
+            );
+            let mut signer = signer.open(wallet, account)?;
+            let addr = place(Side::Bid, signer.as_mut(), args).await?;
+            println!("sent place solution: {}", addr);
+        }
+        Command::PlaceAsk(args) => {
+            println!(
+                "placing ask for {} at price {}",
+                args.amount, args.price
+            );
+            let mut signer = signer.open(wallet, account)?;
+            let addr = place(Side::Ask, signer.as_mut(), args).await?;
+            println!("sent place solution: {}", addr);
+        }
+        Command::Update(args) => {
+            println!(
+                "updating order to amount {} at price {}",
+                args.amount, args.price
+            );
+            let mut signer = signer.open(wallet, account)?;
+            let addr = update(signer.as_mut(), args).await?;
+            println!("sent update solution: {}", addr);
+        }
+        Command::Cancel(args) => {
+            println!("cancelling order");
+            let mut signer = signer.open(wallet, account)?;
+            let addr = cancel(signer.as_mut(), args).await?;
+            println!("sent cancel solution: {}", addr);
+        }
+        Command::Show(args) => {
+            let Show {
+                deployment,
+                node_api,
+            } = args;
+            let deployment = read_deployment(&deployment)?;
+            let node = EssentialNodeClient::new(node_api)?;
+            let bid_amount = query(&node, &deployment, order_app::bid_amount_key()).await?;
+            let price = query(&node, &deployment, order_app::price_key()).await?;
+            let owner = query(&node, &deployment, order_app::owner_key()).await?;
+            let nonce = query(&node, &deployment, order_app::nonce_key()).await?;
+            println!("contract: {}", deployment.contract);
+            println!("token A: {}", deployment.pair.token_a);
+            println!("token B: {}", deployment.pair.token_b);
+            println!("amount: {}", order_app::bid_amount(bid_amount)?);
+            println!("price: {}", order_app::price(price)?);
+            match order_app::owner(owner)? {
+                Some(owner) => println!("owner: {:?}", owner),
+                None => println!("owner: none"),
+            }
+            println!("nonce: {}", order_app::nonce(nonce)?);
+        }
+    }
+    Ok(())
+}
+
+async fn place(side: Side, signer: &mut dyn Signer, args: Place) -> anyhow::Result<ContentAddress> {
+    let Place {
+        amount,
+        price,
+        token_a,
+        token_b,
+        node_api,
+        builder_api,
+        backend_api,
+        deployment: deployment_path,
+    } = args;
+    let owner = signer.hashed_key()?;
+    let pair = Pair::new(
+        ContentAddress(hex_32(&token_a)?),
+        ContentAddress(hex_32(&token_b)?),
+    )?;
+    let node = EssentialNodeClient::new(node_api)?;
+    let builder = EssentialBuilderClient::new(builder_api)?;
+    pair.check_deployed(&node).await?;
+    let market = pair.to_string();
+    let instance = Instance::new(pair);
+
+    // Every order is its own contract instance.
+    let compiled = deploy::compile(&instance).await?;
+    builder.deploy_contract(&compiled.contract).await?;
+    let deployment = compiled.deployment;
+    std::fs::write(&deployment_path, serde_json::to_string_pretty(&deployment)?)?;
+    println!(
+        "deployed order contract {} to {}",
+        deployment.contract,
+        deployment_path.display()
+    );
+
+    let nonce = query(&node, &deployment, order_app::nonce_key()).await?;
+    let init = order_app::place::Init {
+        owner,
+        amount,
+        price,
+        nonce,
+        instance_nonce: deployment.nonce,
+    };
+    let to_sign = order_app::place::data_to_sign(init)?;
+    let build_solution = order_app::place::BuildSolution {
+        predicate: deployment.place.clone(),
+        owner,
+        amount,
+        price,
+        instance_nonce: deployment.nonce,
+        signature: signer.sign_words(&to_sign.to_words())?,
+    };
+    let solution = order_app::place::build_solution(build_solution)?;
+    let ca = builder.submit_solution(&solution).await?;
+
+    // Register the order with the order book so it can be matched.
+    let (route, predicate) = match side {
+        Side::Bid => ("bids", deployment.bid.clone()),
+        Side::Ask => ("asks", deployment.ask.clone()),
+    };
+    let words = order_app::book::order_words(
+        side,
+        &deployment.pair,
+        owner,
+        price,
+        amount,
+        &[predicate.clone(), deployment.update.clone()],
+    );
+    let order = NewOrder {
+        owner,
+        price,
+        quantity: amount,
+        addresses: vec![predicate, deployment.update],
+        signature: essential_sign::encode::signature(&signer.sign_words(&words)?),
+    };
+    let added: serde_json::Value = reqwest::Client::new()
+        .post(format!(
+            "{}/markets/{}/{}",
+            backend_api.trim_end_matches('/'),
+            market,
+            route
+        ))
+        .json(&order)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    println!("added to the order book with id: {}", added["id"]);
+    Ok(ca)
+}
+
+async fn update(signer: &mut dyn Signer, args: Update) -> anyhow::Result<ContentAddress> {
+    let Update {
+        amount,
+        price,
+        deployment,
+        node_api,
+        builder_api,
+    } = args;
+    let deployment = read_deployment(&deployment)?;
+    submit_update(signer, &deployment, amount, price, node_api, builder_api).await
+}
+
+async fn cancel(signer: &mut dyn Signer, args: Cancel) -> anyhow::Result<ContentAddress> {
+    let Cancel {
+        deployment,
+        node_api,
+        builder_api,
+    } = args;
+    let deployment = read_deployment(&deployment)?;
+    let node = EssentialNodeClient::new(node_api.clone())?;
+    let price = order_app::price(query(&node, &deployment, order_app::price_key()).await?)?;
+    // A cancelled order has nothing left to fill.
+    submit_update(signer, &deployment, 0, price, node_api, builder_api).await
+}
+
+/// Builds and submits an `UpdateVars` solution signed by the owner.
+async fn submit_update(
+    signer: &mut dyn Signer,
+    deployment: &Deployment,
+    new_amount: Word,
+    new_price: Word,
+    node_api: String,
+    builder_api: String,
+) -> anyhow::Result<ContentAddress> {
+    let node = EssentialNodeClient::new(node_api)?;
+    let builder = EssentialBuilderClient::new(builder_api)?;
+
+    let nonce = query(&node, deployment, order_app::nonce_key()).await?;
+    let updates = query(&node, deployment, order_app::updates_key()).await?;
+    let Some(owner) = order_app::owner(query(&node, deployment, order_app::owner_key()).await?)? else {
+        anyhow::bail!("Order has not been placed")
+    };
+    let init = order_app::update::Init {
+        new_amount,
+        new_price,
+        nonce,
+        instance_nonce: deployment.nonce,
+        updates,
+    };
+    let to_sign = order_app::update::data_to_sign(init)?;
+    let build_solution = order_app::update::BuildSolution {
+        predicate: deployment.update.clone(),
+        owner,
+        new_amount,
+        new_price,
+        instance_nonce: deployment.nonce,
+        new_updates: to_sign.new_updates,
+        signature: signer.sign_words(&to_sign.to_words())?,
+    };
+    let solution = order_app::update::build_solution(build_solution)?;
+    let ca = builder.submit_solution(&solution).await?;
+    Ok(ca)
+}
+
+/// Queries a key of the order contract instance.
+async fn query(
+    node: &EssentialNodeClient,
+    deployment: &Deployment,
+    key: Key,
+) -> anyhow::Result<Query> {
+    let value = node.query_state(deployment.contract.clone(), key).await?;
+    Ok(Query(value))
+}
+
+/// Reads a deployment written by `place-bid` or `place-ask`.
+fn read_deployment(path: &Path) -> anyhow::Result<Deployment> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Decodes 32 hex encoded bytes.
+fn hex_32(s: &str) -> anyhow::Result<[u8; 32]> {
+    hex::decode(s)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Expected 32 bytes: {}", s))
+}
@@ -0,0 +1,126 @@
+//! # Place
+//! Contains functionality for placing an order by initializing its storage.
+
+use anyhow::ensure;
+use essential_app_utils::inputs::Encode;
+use essential_sign::secp256k1::ecdsa::RecoverableSignature;
+use essential_types::{
+    solution::{Solution, SolutionData},
+    PredicateAddress, Word,
+};
+
+use crate::{is_nil, Query};
+
+/// Represents the initial data required for placing an order.
+pub struct Init {
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The amount of token A to buy or sell.
+    pub amount: Word,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The current nonce of the order instance.
+    pub nonce: Query,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+}
+
+/// Represents the data the owner signs to place an order.
+pub struct ToSign {
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The amount of token A to buy or sell.
+    pub amount: Word,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+}
+
+/// Contains all necessary information to build a place solution.
+pub struct BuildSolution {
+    /// The address of the order instance's `Place` predicate.
+    pub predicate: PredicateAddress,
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The amount of token A to buy or sell.
+    pub amount: Word,
+    /// The price of token A in token B.
+    pub price: Word,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+    /// The owner's signature over the data.
+    pub signature: RecoverableSignature,
+}
+
+/// Prepares the data the owner signs to place an order.
+pub fn data_to_sign(init: Init) -> anyhow::Result<ToSign> {
+    let Init {
+        owner,
+        amount,
+        price,
+        nonce,
+        instance_nonce,
+    } = init;
+    ensure!(amount > 0, "Amount must be greater than zero");
+    ensure!(price > 0, "Price must be greater than zero");
+    ensure!(is_nil(&nonce), "Order has already been placed");
+    Ok(ToSign {
+        owner,
+        amount,
+        price,
+        instance_nonce,
+    })
+}
+
+/// Builds a place solution based on the provided data.
+///
+/// Fails if the signature is not the owner's signature over the data.
+pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
+    let BuildSolution {
+        predicate,
+        owner,
+        amount,
+        price,
+        instance_nonce,
+        signature,
+    } = build;
+    let to_sign = ToSign {
+        owner,
+        amount,
+        price,
+        instance_nonce,
+    };
+    token::verify::verify_key(&to_sign.to_words(), &signature, owner)?;
+    let vars = super::order::Place::Vars {
+        amount,
+        order_price: price,
+        order_owner: owner,
+        sig: signature.encode(),
+    };
+    let mutations = super::order::storage::mutations()
+        .bid_amount(amount)
+        .price(price)
+        .owner(owner)
+        .nonce(instance_nonce);
+    let solution = SolutionData {
+        predicate_to_solve: predicate,
+        decision_variables: vars.into(),
+        transient_data: Default::default(),
+        state_mutations: mutations.into(),
+    };
+    Ok(Solution {
+        data: vec![solution],
+    })
+}
+
+impl ToSign {
+    /// Converts the ToSign struct to a vector of Words for signing.
+    pub fn to_words(&self) -> Vec<Word> {
+        self.owner
+            .iter()
+            .copied()
+            .chain([self.amount, self.price, self.instance_nonce])
+            .collect()
+    }
+}
@@ -0,0 +1,134 @@
+//! # Update
+//! Contains functionality for updating the amount and price of an order.
+//!
+//! Only the owner can update an order. Each update is signed over the
+//! order's next update count, so a signed update can never be replayed.
+
+use anyhow::ensure;
+use essential_app_utils::inputs::Encode;
+use essential_sign::secp256k1::ecdsa::RecoverableSignature;
+use essential_types::{
+    solution::{Solution, SolutionData},
+    PredicateAddress, Word,
+};
+
+use crate::{nonce as instance, updates, Query};
+
+/// Represents the initial data required for updating an order.
+pub struct Init {
+    /// The new remaining amount of the order.
+    pub new_amount: Word,
+    /// The new price of the order.
+    pub new_price: Word,
+    /// The current nonce of the order instance.
+    pub nonce: Query,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+    /// The current update count of the order instance.
+    pub updates: Query,
+}
+
+/// Represents the data the owner signs to update an order.
+pub struct ToSign {
+    /// The new remaining amount of the order.
+    pub new_amount: Word,
+    /// The new price of the order.
+    pub new_price: Word,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+    /// The update count after this update.
+    pub new_updates: Word,
+}
+
+/// Contains all necessary information to build an update solution.
+pub struct BuildSolution {
+    /// The address of the order instance's `UpdateVars` predicate.
+    pub predicate: PredicateAddress,
+    /// The hashed key of the order owner.
+    pub owner: [Word; 4],
+    /// The new remaining amount of the order.
+    pub new_amount: Word,
+    /// The new price of the order.
+    pub new_price: Word,
+    /// The unique nonce the order instance was compiled with.
+    pub instance_nonce: Word,
+    /// The update count after this update.
+    pub new_updates: Word,
+    /// The owner's signature over the data.
+    pub signature: RecoverableSignature,
+}
+
+/// Prepares the data the owner signs to update an order.
+pub fn data_to_sign(init: Init) -> anyhow::Result<ToSign> {
+    let Init {
+        new_amount,
+        new_price,
+        nonce,
+        instance_nonce,
+        updates: current_updates,
+    } = init;
+    ensure!(new_amount >= 0, "Amount must not be negative");
+    ensure!(new_price >= 0, "Price must not be negative");
+    ensure!(
+        instance(nonce)? == instance_nonce,
+        "Order has not been placed"
+    );
+    Ok(ToSign {
+        new_amount,
+        new_price,
+        instance_nonce,
+        new_updates: updates(current_updates)? + 1,
+    })
+}
+
+/// Builds an update solution based on the provided data.
+///
+/// Fails if the signature is not the owner's signature over the data.
+pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
+    let BuildSolution {
+        predicate,
+        owner,
+        new_amount,
+        new_price,
+        instance_nonce,
+        new_updates,
+        signature,
+    } = build;
+    let to_sign = ToSign {
+        new_amount,
+        new_price,
+        instance_nonce,
+        new_updates,
+    };
+    token::verify::verify_key(&to_sign.to_words(), &signature, owner)?;
+    let vars = super::order::UpdateVars::Vars {
+        new_amount,
+        new_price,
+        sig: signature.encode(),
+    };
+    let mutations = super::order::storage::mutations()
+        .bid_amount(new_amount)
+        .price(new_price)
+        .updates(new_updates);
+    let solution = SolutionData {
+        predicate_to_solve: predicate,
+        decision_variables: vars.into(),
+        transient_data: Default::default(),
+        state_mutations: mutations.into(),
+    };
+    Ok(Solution {
+        data: vec![solution],
+    })
+}
+
+impl ToSign {
+    /// Converts the ToSign struct to a vector of Words for signing.
+    pub fn to_words(&self) -> Vec<Word> {
+        vec![
+            self.new_amount,
+            self.new_price,
+            self.instance_nonce,
+            self.new_updates,
+        ]
+    }
+}
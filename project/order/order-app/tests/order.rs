@@ -0,0 +1,321 @@
+use essential_app_utils::{self as utils, compile::compile_pint_project, db::Dbs};
+use essential_sign::secp256k1::SecretKey;
+use essential_types::{contract::Contract, solution::Solution, ContentAddress, Key, Value, Word};
+use order_app::deploy::{self, Deployment, Instance, Pair};
+use token::signer::{KeySigner, Signer};
+
+// Constants for the test
+
+/// The private key of the token minter, who places the bid.
+const ALICE_KEY: &str = "128A3D2146A69581FD8FC4C0A9B7A96A5755D85255D4E47F814AFA69D7726C8D";
+/// The private key of the account that places the ask.
+const BOB_KEY: &str = "4B1F0D9E3C2A1908F7E6D5C4B3A29180F7E6D5C4B3A29180F7E6D5C4B3A29180";
+/// The amount of each token minted to Alice.
+const MINT_AMOUNT: Word = 1_000_000;
+/// The amount of token A Alice sends Bob so he has something to sell.
+const BOB_TOKEN_A: Word = 1_000;
+/// The price the ask is placed at and the fill trades at, in token B per token A.
+const PRICE: Word = 3;
+/// The price the bid is placed at, above the ask so the book crosses.
+const BID_PRICE: Word = 4;
+/// The amount of token A Alice bids for.
+const BID_AMOUNT: Word = 500;
+/// The amount of token A Bob asks to sell.
+const ASK_AMOUNT: Word = 200;
+
+#[tokio::test]
+async fn place_and_settle() {
+    // Compile the token contract
+    // This requires `pint` be available on PATH
+    let token_a =
+        compile_pint_project(concat!(env!("CARGO_MANIFEST_DIR"), "/../../token/pint/token").into())
+            .await
+            .unwrap();
+
+    // A second token with the same code but its own address
+    let token_b = Contract {
+        salt: [1; 32],
+        ..token_a.clone()
+    };
+    let addr_a = essential_hash::contract_addr::from_contract(&token_a);
+    let addr_b = essential_hash::contract_addr::from_contract(&token_b);
+
+    let mut alice = signer(ALICE_KEY);
+    let mut bob = signer(BOB_KEY);
+    let alice_key = alice.hashed_key().unwrap();
+    let bob_key = bob.hashed_key().unwrap();
+
+    // Create new databases for testing
+    let dbs = utils::db::new_dbs().await;
+
+    // Deploy both tokens and mint them to Alice
+    for token in [&token_a, &token_b] {
+        utils::deploy::deploy_contract(&dbs.builder, token)
+            .await
+            .unwrap();
+    }
+    for token in [&addr_a, &addr_b] {
+        let solution = mint(&dbs, token, &mut alice).await;
+        submit(&dbs, solution).await;
+    }
+    let solution = transfer(&dbs, &addr_a, &mut alice, bob_key, BOB_TOKEN_A).await;
+    submit(&dbs, solution).await;
+
+    // Compile and deploy an order instance for each side of the book
+    let pair = Pair::new(addr_a.clone(), addr_b.clone()).unwrap();
+    let bid = deploy_order(&dbs, &pair).await;
+    let ask = deploy_order(&dbs, &pair).await;
+    assert_ne!(bid.contract, ask.contract);
+    assert_eq!(bid.pair, pair);
+
+    // Place the bid for Alice and the ask for Bob
+    for (deployment, owner, amount, price) in [
+        (&bid, &mut alice, BID_AMOUNT, BID_PRICE),
+        (&ask, &mut bob, ASK_AMOUNT, PRICE),
+    ] {
+        let nonce = query(&dbs, &deployment.contract, &order_app::nonce_key()).await;
+        let init = order_app::place::Init {
+            owner: owner.hashed_key().unwrap(),
+            amount,
+            price,
+            nonce: order_app::Query(nonce),
+            instance_nonce: deployment.nonce,
+        };
+        let to_sign = order_app::place::data_to_sign(init).unwrap();
+        let build = order_app::place::BuildSolution {
+            predicate: deployment.place.clone(),
+            owner: to_sign.owner,
+            amount,
+            price,
+            instance_nonce: deployment.nonce,
+            signature: owner.sign_words(&to_sign.to_words()).unwrap(),
+        };
+        let solution = order_app::place::build_solution(build).unwrap();
+        submit(&dbs, solution).await;
+    }
+    for (deployment, owner, amount, price) in [
+        (&bid, alice_key, BID_AMOUNT, BID_PRICE),
+        (&ask, bob_key, ASK_AMOUNT, PRICE),
+    ] {
+        assert_eq!(
+            order_state(&dbs, deployment).await,
+            (amount, price, deployment.nonce)
+        );
+        let owner_state = query(&dbs, &deployment.contract, &order_app::owner_key()).await;
+        assert_eq!(
+            order_app::owner(order_app::Query(owner_state)).unwrap(),
+            Some(owner)
+        );
+    }
+
+    // Settle the ask against the bid at the ask's price
+    // Bob sends token A to Alice and Alice pays Bob in token B
+    let fill = ASK_AMOUNT;
+    let bid_amount = query(&dbs, &bid.contract, &order_app::bid_amount_key()).await;
+    let bid_price = query(&dbs, &bid.contract, &order_app::price_key()).await;
+    let bid_nonce = query(&dbs, &bid.contract, &order_app::nonce_key()).await;
+    let bid_init = |fill_price| order_app::bid::Init {
+        predicate: bid.bid.clone(),
+        amount: fill,
+        fill_price,
+        bid_amount: order_app::Query(bid_amount.clone()),
+        price: order_app::Query(bid_price.clone()),
+        nonce: order_app::Query(bid_nonce.clone()),
+        instance_nonce: bid.nonce,
+    };
+    // A bid never pays more than its own price
+    assert!(order_app::bid::init(bid_init(BID_PRICE + 1)).is_err());
+    let bid_fill = order_app::bid::init(bid_init(PRICE)).unwrap();
+    let ask_amount = query(&dbs, &ask.contract, &order_app::bid_amount_key()).await;
+    let ask_price = query(&dbs, &ask.contract, &order_app::price_key()).await;
+    let ask_nonce = query(&dbs, &ask.contract, &order_app::nonce_key()).await;
+    let ask_init = |fill_price| order_app::ask::Init {
+        predicate: ask.ask.clone(),
+        amount: fill,
+        fill_price,
+        bid_amount: order_app::Query(ask_amount.clone()),
+        price: order_app::Query(ask_price.clone()),
+        nonce: order_app::Query(ask_nonce.clone()),
+        instance_nonce: ask.nonce,
+    };
+    // An ask never receives less than its own price
+    assert!(order_app::ask::init(ask_init(PRICE - 1)).is_err());
+    let ask_fill = order_app::ask::init(ask_init(PRICE)).unwrap();
+
+    let mut settlement = Solution { data: vec![] };
+    settlement
+        .data
+        .extend(order_app::bid::build_solution(bid_fill).unwrap().data);
+    settlement
+        .data
+        .extend(order_app::ask::build_solution(ask_fill).unwrap().data);
+    settlement.data.extend(
+        transfer(&dbs, &addr_a, &mut bob, alice_key, fill)
+            .await
+            .data,
+    );
+    settlement.data.extend(
+        transfer(&dbs, &addr_b, &mut alice, bob_key, fill * PRICE)
+            .await
+            .data,
+    );
+    submit(&dbs, settlement).await;
+
+    // Verify the orders after the fill
+    assert_eq!(
+        order_state(&dbs, &bid).await,
+        (BID_AMOUNT - fill, BID_PRICE, bid.nonce)
+    );
+    assert_eq!(
+        order_state(&dbs, &ask).await,
+        (ASK_AMOUNT - fill, PRICE, ask.nonce)
+    );
+
+    // Verify the balances after the fill
+    assert_eq!(
+        balance(&dbs, &addr_a, alice_key).await,
+        MINT_AMOUNT - BOB_TOKEN_A + fill
+    );
+    assert_eq!(balance(&dbs, &addr_a, bob_key).await, BOB_TOKEN_A - fill);
+    assert_eq!(
+        balance(&dbs, &addr_b, alice_key).await,
+        MINT_AMOUNT - fill * PRICE
+    );
+    assert_eq!(balance(&dbs, &addr_b, bob_key).await, fill * PRICE);
+
+    // Only the owner can cancel an order
+    let nonce = query(&dbs, &bid.contract, &order_app::nonce_key()).await;
+    let updates = query(&dbs, &bid.contract, &order_app::updates_key()).await;
+    let to_sign = order_app::update::data_to_sign(order_app::update::Init {
+        new_amount: 0,
+        new_price: BID_PRICE,
+        nonce: order_app::Query(nonce),
+        instance_nonce: bid.nonce,
+        updates: order_app::Query(updates),
+    })
+    .unwrap();
+    let cancel = |signer: &mut KeySigner| order_app::update::BuildSolution {
+        predicate: bid.update.clone(),
+        owner: alice_key,
+        new_amount: 0,
+        new_price: BID_PRICE,
+        instance_nonce: bid.nonce,
+        new_updates: to_sign.new_updates,
+        signature: signer.sign_words(&to_sign.to_words()).unwrap(),
+    };
+    assert!(order_app::update::build_solution(cancel(&mut bob)).is_err());
+    let solution = order_app::update::build_solution(cancel(&mut alice)).unwrap();
+    submit(&dbs, solution).await;
+    assert_eq!(order_state(&dbs, &bid).await, (0, BID_PRICE, bid.nonce));
+}
+
+// Helper function to create a signer from a hex encoded private key
+fn signer(key: &str) -> KeySigner {
+    KeySigner::new(SecretKey::from_slice(&hex::decode(key).unwrap()).unwrap())
+}
+
+// Helper function to compile and deploy an order instance
+async fn deploy_order(dbs: &Dbs, pair: &Pair) -> Deployment {
+    let compiled = deploy::compile(&Instance::new(pair.clone())).await.unwrap();
+    utils::deploy::deploy_contract(&dbs.builder, &compiled.contract)
+        .await
+        .unwrap();
+    compiled.deployment
+}
+
+// Helper function to build a mint of the token supply to the signer
+async fn mint(dbs: &Dbs, token: &ContentAddress, signer: &mut KeySigner) -> Solution {
+    let key = signer.hashed_key().unwrap();
+    let nonce = query(dbs, token, &token::nonce_key(key)).await;
+    let init = token::mint::Init {
+        hashed_key: key,
+        amount: MINT_AMOUNT,
+        decimals: 18,
+        nonce: token::Query(nonce),
+    };
+    let to_sign = token::mint::data_to_sign(init).unwrap();
+    let balance = query(dbs, token, &token::balance_key(key)).await;
+    let build = token::mint::BuildSolution {
+        token: token.clone(),
+        new_nonce: to_sign.new_nonce,
+        current_balance: token::Query(balance),
+        hashed_key: key,
+        amount: MINT_AMOUNT,
+        decimals: 18,
+        signature: signer.sign_words(&to_sign.to_words()).unwrap(),
+        token_name: "alice coin".to_string(),
+        token_symbol: "ALC".to_string(),
+    };
+    token::mint::build_solution(build).unwrap()
+}
+
+// Helper function to build a signed transfer on a token
+async fn transfer(
+    dbs: &Dbs,
+    token: &ContentAddress,
+    from: &mut KeySigner,
+    to: [Word; 4],
+    amount: Word,
+) -> Solution {
+    let from_key = from.hashed_key().unwrap();
+    let nonce = query(dbs, token, &token::nonce_key(from_key)).await;
+    let init = token::transfer::Init {
+        hashed_from_key: from_key,
+        hashed_to_key: to,
+        amount,
+        nonce: token::Query(nonce.clone()),
+    };
+    let to_sign = token::transfer::data_to_sign(init).unwrap();
+    let from_balance = query(dbs, token, &token::balance_key(from_key)).await;
+    let to_balance = query(dbs, token, &token::balance_key(to)).await;
+    let build = token::transfer::BuildSolution {
+        token: token.clone(),
+        hashed_from_key: from_key,
+        hashed_to_key: to,
+        new_nonce: to_sign.new_nonce,
+        amount,
+        current_nonce: token::Query(nonce),
+        current_from_balance: token::Query(from_balance),
+        current_to_balance: token::Query(to_balance),
+        signature: from.sign_words(&to_sign.to_words()).unwrap(),
+    };
+    token::transfer::build_solution(build).unwrap()
+}
+
+// Helper function to submit, validate and build a solution into a block
+async fn submit(dbs: &Dbs, solution: Solution) {
+    utils::builder::submit(&dbs.builder, solution.clone())
+        .await
+        .unwrap();
+    utils::node::validate_solution(&dbs.node, solution)
+        .await
+        .unwrap();
+    let o = utils::builder::build_default(dbs).await.unwrap();
+    assert!(o.failed.is_empty(), "{:?}", o.failed);
+}
+
+// Helper function to query a key of a contract
+async fn query(dbs: &Dbs, contract: &ContentAddress, key: &Key) -> Option<Value> {
+    utils::node::query_state_head(&dbs.node, contract, key)
+        .await
+        .unwrap()
+}
+
+// Helper function to read the remaining amount, price and nonce of an order
+async fn order_state(dbs: &Dbs, deployment: &Deployment) -> (Word, Word, Word) {
+    let contract = &deployment.contract;
+    let amount = query(dbs, contract, &order_app::bid_amount_key()).await;
+    let price = query(dbs, contract, &order_app::price_key()).await;
+    let nonce = query(dbs, contract, &order_app::nonce_key()).await;
+    (
+        order_app::bid_amount(order_app::Query(amount)).unwrap(),
+        order_app::price(order_app::Query(price)).unwrap(),
+        order_app::nonce(order_app::Query(nonce)).unwrap(),
+    )
+}
+
+// Helper function to read an account's balance of a token
+async fn balance(dbs: &Dbs, token: &ContentAddress, key: [Word; 4]) -> Word {
+    let balance = query(dbs, token, &token::balance_key(key)).await;
+    token::balance(token::Query(balance)).unwrap()
+}
@@ -3,10 +3,16 @@ use essential_app_utils::inputs::Encode;
 use essential_sign::secp256k1::ecdsa::RecoverableSignature;
 use essential_types::{
     solution::{Solution, SolutionData},
-    Word,
+    ContentAddress, Word,
 };
 
-use crate::{balance, nonce, Query};
+use crate::{
+    balance,
+    check::{PreState, Slot},
+    nonce, on_token,
+    rules::{debit, positive_amount},
+    Query,
+};
 
 /// Represents the initial data required for burning tokens.
 pub struct Init {
@@ -30,8 +36,12 @@ pub struct ToSign {
 
 /// Contains all necessary information to build a burn solution.
 pub struct BuildSolution {
+    /// The address of the token contract.
+    pub token: ContentAddress,
     /// The new nonce of the account.
     pub new_nonce: Word,
+    /// The current nonce of the account, as queried before signing.
+    pub current_nonce: Query,
     /// The current balance of the account.
     pub current_balance: Query,
     /// The hashed key of the account.
@@ -43,12 +53,15 @@ pub struct BuildSolution {
 }
 
 /// Prepares the data to be signed for a burn transaction.
+///
+/// Fails with an [`Error`](crate::Error) if the amount is not positive.
 pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
     let Init {
         hashed_key,
         nonce: current_nonce,
         amount,
     } = account;
+    positive_amount(amount)?;
     let new_nonce = increment_nonce(nonce(current_nonce)?);
     Ok(ToSign {
         hashed_key,
@@ -58,16 +71,30 @@ pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
 }
 
 /// Builds a burn solution based on the provided data.
+///
+/// Fails with an [`Error`](crate::Error) if the burn breaks a rule of the contract.
 pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
     let BuildSolution {
+        token,
         new_nonce,
+        current_nonce,
         current_balance,
         hashed_key,
         amount,
         signature,
     } = build;
+    let to_sign = ToSign {
+        hashed_key,
+        amount,
+        new_nonce,
+    };
+    crate::verify::burn(&to_sign, &signature)?;
+    positive_amount(amount)?;
+    let pre = PreState::default()
+        .with(Slot::Balance(hashed_key), &current_balance)
+        .with(Slot::Nonce(hashed_key), &current_nonce);
     let from_balance = balance(current_balance)?;
-    let new_from_balance = calculate_from_balance(from_balance, amount)?;
+    let new_from_balance = debit(from_balance, amount)?;
     let pub_vars = super::token::Burn::PubVars {
         key: hashed_key,
         amount,
@@ -80,11 +107,12 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
         .balances(|map| map.entry(hashed_key, new_from_balance))
         .nonce(|nonces| nonces.entry(hashed_key, new_nonce));
     let solution = SolutionData {
-        predicate_to_solve: super::token::Burn::ADDRESS,
+        predicate_to_solve: on_token(&token, super::token::Burn::ADDRESS),
         decision_variables: vars.into(),
         transient_data: pub_vars.into(),
         state_mutations: mutations.into(),
     };
+    crate::check::solution_data(&pre, &token, &solution)?;
     Ok(Solution {
         data: vec![solution],
     })
@@ -95,13 +123,6 @@ fn increment_nonce(nonce: Word) -> Word {
     nonce + 1
 }
 
-/// Calculates the new balance after burning tokens.
-fn calculate_from_balance(from_balance: Word, amount: Word) -> anyhow::Result<Word> {
-    from_balance
-        .checked_sub(amount)
-        .ok_or(anyhow::anyhow!("Insufficient balance"))
-}
-
 impl ToSign {
     /// Converts the ToSign struct to a vector of Words for signing.
     pub fn to_words(&self) -> Vec<Word> {
@@ -0,0 +1,281 @@
+//! # Check
+//! Evaluates the token predicates' state constraints in Rust.
+//!
+//! The pre-state is the storage read by the predicate and the post-state is
+//! the pre-state with the solution's mutations applied. This catches
+//! mutations that drift from the contract before the node rejects them.
+//! Authorization is checked separately by [`crate::verify`].
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+use essential_types::{solution::SolutionData, ContentAddress, Key, Value, Word};
+
+use crate::{
+    decode::{self, optional_word, Decoded, StateMutation},
+    Error, Query,
+};
+
+/// `config::NAME`, the hash of the token name.
+pub const NAME: [Word; 4] = [
+    3811456199330662851,
+    -3885622559525504162,
+    -3842701681523739488,
+    1075057600795388195,
+];
+
+/// `config::SYMBOL`, the hash of the token symbol.
+pub const SYMBOL: [Word; 4] = [
+    -7931778949389842513,
+    -4107189354163715774,
+    5605645370890485344,
+    -5951329217151790972,
+];
+
+/// `config::MINT_KEY`, the only key that can mint.
+pub const MINT_KEY: [Word; 4] = [
+    2219060783369705254,
+    1903643416962669404,
+    -3002852850971091803,
+    -561034547036525023,
+];
+
+/// A storage slot of the token contract.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// `storage::balances[key]`.
+    Balance([Word; 4]),
+    /// `storage::nonce[key]`.
+    Nonce([Word; 4]),
+    /// `storage::token_name`.
+    TokenName,
+    /// `storage::token_symbol`.
+    TokenSymbol,
+    /// `storage::decimals`.
+    Decimals,
+    /// A key that is not part of the token storage.
+    Other(Key),
+}
+
+/// The pre-state of the token storage read by a solution.
+///
+/// Slots that are not set are nil.
+#[derive(Clone, Debug, Default)]
+pub struct PreState(HashMap<Slot, Value>);
+
+/// The pre and post value of a single word slot.
+#[derive(Clone, Copy)]
+struct Transition {
+    pre: Option<Word>,
+    post: Option<Word>,
+}
+
+/// The pre-state with a solution's mutations applied.
+struct PostState<'a> {
+    pre: &'a PreState,
+    mutations: HashMap<Slot, Value>,
+}
+
+impl PreState {
+    /// Sets a slot to the result of a state query.
+    pub fn with(mut self, slot: Slot, value: &Query) -> Self {
+        self.insert(slot, value.0.clone().unwrap_or_default());
+        self
+    }
+
+    /// Sets a slot to a value.
+    /// An empty value is nil.
+    pub fn insert(&mut self, slot: Slot, value: Value) {
+        self.0.insert(slot, value);
+    }
+}
+
+/// Checks a `SolutionData` to the token contract deployed at `token` against the pre-state.
+pub fn solution_data(
+    pre: &PreState,
+    token: &ContentAddress,
+    data: &SolutionData,
+) -> anyhow::Result<()> {
+    match decode::solution_data(data, token)? {
+        Decoded::Mint(data) => mint(pre, &data),
+        Decoded::Burn(data) => burn(pre, &data),
+        Decoded::Transfer(data) => transfer(pre, &data),
+        Decoded::Cancel(data) => cancel(pre, &data),
+        Decoded::Unknown(address) => bail!("Not a token predicate: {:?}", address),
+    }
+}
+
+/// Checks the constraints of the `Mint` predicate.
+pub fn mint(pre: &PreState, data: &decode::Mint) -> anyhow::Result<()> {
+    let state = PostState::new(pre, &data.mutations)?;
+    let balance = state.int(Slot::Balance(data.key))?;
+    let nonce = state.int(Slot::Nonce(data.key))?;
+    let token_decimals = state.int(Slot::Decimals)?;
+    let token_name = state.value(Slot::TokenName);
+    let token_symbol = state.value(Slot::TokenSymbol);
+    check(data.key == MINT_KEY, "key == config::MINT_KEY")?;
+    check(
+        init_once(balance, data.amount),
+        "@init_once(balance; amount)",
+    )?;
+    check(
+        token_name.0.is_empty() && token_name.1 == NAME,
+        "@init_once(token_name; config::NAME)",
+    )?;
+    check(
+        token_symbol.0.is_empty() && token_symbol.1 == SYMBOL,
+        "@init_once(token_symbol; config::SYMBOL)",
+    )?;
+    check(
+        init_once(token_decimals, data.decimals),
+        "@init_once(token_decimals; decimals)",
+    )?;
+    check(init_once(nonce, 1), "@init_once(nonce; 1)")?;
+    Ok(())
+}
+
+/// Checks the constraints of the `Burn` predicate.
+pub fn burn(pre: &PreState, data: &decode::Burn) -> anyhow::Result<()> {
+    let state = PostState::new(pre, &data.mutations)?;
+    let balance = state.int(Slot::Balance(data.key))?;
+    let nonce = state.int(Slot::Nonce(data.key))?;
+    check(data.amount > 0, "amount > 0")?;
+    check(
+        delta(balance) == negate(data.amount),
+        "@delta(balance) == 0 - amount",
+    )?;
+    check(balance.post.is_some_and(|b| b >= 0), "balance' >= 0")?;
+    check(safe_increment(nonce), "@safe_increment(nonce)")?;
+    Ok(())
+}
+
+/// Checks the constraints of the `Transfer` predicate.
+pub fn transfer(pre: &PreState, data: &decode::Transfer) -> anyhow::Result<()> {
+    let state = PostState::new(pre, &data.mutations)?;
+    let sender_balance = state.int(Slot::Balance(data.key))?;
+    let receiver_balance = state.int(Slot::Balance(data.to))?;
+    let nonce = state.int(Slot::Nonce(data.key))?;
+    check(data.amount > 0, "amount > 0")?;
+    check(
+        sender_balance.post.is_some_and(|b| b >= 0),
+        "sender_balance' >= 0",
+    )?;
+    check(
+        delta(sender_balance) == negate(data.amount),
+        "@delta(sender_balance) == 0 - amount",
+    )?;
+    check(
+        init_delta(receiver_balance, data.amount),
+        "@init_delta(receiver_balance; amount)",
+    )?;
+    check(safe_increment(nonce), "@safe_increment(nonce)")?;
+    Ok(())
+}
+
+/// Checks the constraints of the `Cancel` predicate.
+pub fn cancel(pre: &PreState, data: &decode::Cancel) -> anyhow::Result<()> {
+    let state = PostState::new(pre, &data.mutations)?;
+    let nonce = state.int(Slot::Nonce(data.key))?;
+    check(safe_increment(nonce), "@safe_increment(nonce)")?;
+    Ok(())
+}
+
+impl<'a> PostState<'a> {
+    /// Applies the mutations to the pre-state.
+    /// Fails if more than one mutation sets the same slot.
+    fn new(pre: &'a PreState, mutations: &[StateMutation]) -> Result<Self, Error> {
+        let mut post = HashMap::new();
+        for mutation in mutations {
+            let (slot, value) = slot_value(mutation);
+            if post.insert(slot.clone(), value).is_some() {
+                return Err(Error::ConflictingMutations(slot));
+            }
+        }
+        Ok(Self {
+            pre,
+            mutations: post,
+        })
+    }
+
+    /// Returns the pre and post value of a slot.
+    fn value(&self, slot: Slot) -> (Value, Value) {
+        let pre = self.pre.0.get(&slot).cloned().unwrap_or_default();
+        let post = self.mutations.get(&slot).cloned().unwrap_or(pre.clone());
+        (pre, post)
+    }
+
+    /// Returns the pre and post value of a single word slot.
+    fn int(&self, slot: Slot) -> anyhow::Result<Transition> {
+        let (pre, post) = self.value(slot);
+        Ok(Transition {
+            pre: optional_word(&pre)?,
+            post: optional_word(&post)?,
+        })
+    }
+}
+
+/// Returns the storage slot and new value of a mutation.
+fn slot_value(mutation: &StateMutation) -> (Slot, Value) {
+    let word = |value: &Option<Word>| value.map(|v| vec![v]).unwrap_or_default();
+    match mutation {
+        StateMutation::Balance { key, value } => (Slot::Balance(*key), word(value)),
+        StateMutation::Nonce { key, value } => (Slot::Nonce(*key), word(value)),
+        StateMutation::TokenName(value) => {
+            (Slot::TokenName, value.map(Vec::from).unwrap_or_default())
+        }
+        StateMutation::TokenSymbol(value) => {
+            (Slot::TokenSymbol, value.map(Vec::from).unwrap_or_default())
+        }
+        StateMutation::Decimals(value) => (Slot::Decimals, word(value)),
+        StateMutation::Unknown(mutation) => {
+            (Slot::Other(mutation.key.clone()), mutation.value.clone())
+        }
+    }
+}
+
+/// Fails with the constraint if it is not satisfied.
+fn check(satisfied: bool, constraint: &'static str) -> Result<(), Error> {
+    if satisfied {
+        Ok(())
+    } else {
+        Err(Error::Unsatisfied(constraint))
+    }
+}
+
+/// `$s' - $s`, which is nil if either side is nil.
+fn delta(s: Transition) -> Option<Word> {
+    s.post?.checked_sub(s.pre?)
+}
+
+/// `0 - $v`.
+fn negate(v: Word) -> Option<Word> {
+    0.checked_sub(v)
+}
+
+/// `$s == nil && $s' == $v`.
+fn init_once(s: Transition, v: Word) -> bool {
+    s.pre.is_none() && s.post == Some(v)
+}
+
+/// `($s == nil && $s' == $eq) || @delta($s) == $eq`.
+fn init_delta(s: Transition, eq: Word) -> bool {
+    init_once(s, eq) || delta(s) == Some(eq)
+}
+
+/// `($s == nil && $s' == 1) || @delta($s) == 1`.
+fn safe_increment(s: Transition) -> bool {
+    init_delta(s, 1)
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Slot::Balance(key) => write!(f, "balances[{:?}]", key),
+            Slot::Nonce(key) => write!(f, "nonce[{:?}]", key),
+            Slot::TokenName => write!(f, "token_name"),
+            Slot::TokenSymbol => write!(f, "token_symbol"),
+            Slot::Decimals => write!(f, "decimals"),
+            Slot::Other(key) => write!(f, "{:?}", key),
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! # Decode
+//! Turns the `SolutionData` of token predicates back into typed structs.
+//!
+//! Decision variables are decoded from their flattened word layout.
+//! Unions are encoded as a tag word followed by the variant,
+//! padded to the size of the largest variant.
+
+use anyhow::{bail, ensure};
+use essential_types::{
+    convert::u8_32_from_word_4,
+    solution::{Mutation, Solution, SolutionData},
+    ContentAddress, Key, PredicateAddress, Signature, Word,
+};
+
+use crate::on_token;
+
+/// Number of words in an encoded `Secp256k1Signature`.
+const SIGNATURE_WORDS: usize = 9;
+/// Number of words in an encoded `PredicateAddress`.
+const ADDRESS_WORDS: usize = 8;
+/// Number of words in an encoded `BurnAuth`, `MintAuth` or `CancelAuth`.
+const AUTH_WORDS: usize = 1 + SIGNATURE_WORDS;
+/// Number of words in an encoded `TransferAuthMode`.
+const TRANSFER_AUTH_MODE_WORDS: usize = 1 + SIGNATURE_WORDS + 1;
+/// Number of words in an encoded `ExtraConstraints`.
+const EXTRA_CONSTRAINTS_WORDS: usize = 1 + ADDRESS_WORDS + 1;
+
+/// A decoded token predicate solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decoded {
+    /// A solution to the `Mint` predicate.
+    Mint(Mint),
+    /// A solution to the `Burn` predicate.
+    Burn(Burn),
+    /// A solution to the `Transfer` predicate.
+    Transfer(Transfer),
+    /// A solution to the `Cancel` predicate.
+    Cancel(Cancel),
+    /// A solution to a predicate that is not part of the token contract.
+    Unknown(PredicateAddress),
+}
+
+/// A decoded mint solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mint {
+    /// The hashed key that is being minted to.
+    pub key: [Word; 4],
+    /// The amount of tokens minted.
+    pub amount: Word,
+    /// The number of decimals of the token.
+    pub decimals: Word,
+    /// The authorization for the mint.
+    pub auth: Auth,
+    /// The state mutations proposed by the solution.
+    pub mutations: Vec<StateMutation>,
+}
+
+/// A decoded burn solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Burn {
+    /// The hashed key of the account burning tokens.
+    pub key: [Word; 4],
+    /// The amount of tokens burnt.
+    pub amount: Word,
+    /// The authorization for the burn.
+    pub auth: Auth,
+    /// The state mutations proposed by the solution.
+    pub mutations: Vec<StateMutation>,
+}
+
+/// A decoded transfer solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    /// The hashed key of the sender.
+    pub key: [Word; 4],
+    /// The hashed key of the recipient.
+    pub to: [Word; 4],
+    /// The amount of tokens transferred.
+    pub amount: Word,
+    /// The authorization mode for the transfer.
+    pub auth: TransferAuth,
+    /// The extra constraints for the transfer.
+    pub extra: ExtraConstraints,
+    /// The state mutations proposed by the solution.
+    pub mutations: Vec<StateMutation>,
+}
+
+/// A decoded cancel solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cancel {
+    /// The hashed key of the account cancelling.
+    pub key: [Word; 4],
+    /// The authorization for the cancel.
+    pub auth: Auth,
+    /// The state mutations proposed by the solution.
+    pub mutations: Vec<StateMutation>,
+}
+
+/// The authorization used by the mint, burn and cancel predicates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Auth {
+    /// Authorized by a signature from the key.
+    Signed(Signature),
+    /// Authorized by a predicate that owns the key.
+    Predicate(PredicateAddress),
+}
+
+/// The authorization mode used by the transfer predicate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransferAuth {
+    /// Authorized by a signature from the key.
+    Signed {
+        /// The signature over the data selected by `mode`.
+        signature: Signature,
+        /// Which of the transfer fields were signed.
+        mode: SignedMode,
+    },
+    /// Authorized by a predicate that owns the key.
+    Predicate(PredicateAddress),
+}
+
+/// Which of the transfer fields are covered by the signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignedMode {
+    /// The key, recipient, amount and nonce.
+    All,
+    /// The key and nonce.
+    Key,
+    /// The key, recipient and nonce.
+    KeyTo,
+    /// The key, amount and nonce.
+    KeyAmount,
+}
+
+/// Optional extra constraints for the transfer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtraConstraints {
+    /// The predicate at `path` in the solution must be `addr`.
+    Extra {
+        /// The predicate that must be solved alongside the transfer.
+        addr: PredicateAddress,
+        /// The index of that predicate in the solution.
+        path: Word,
+    },
+    /// No extra constraints.
+    None,
+}
+
+/// A decoded mutation to the token contract's storage.
+///
+/// A value of `None` means the mutation sets the value to nil.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateMutation {
+    /// Sets the balance of an account.
+    Balance {
+        /// The hashed key of the account.
+        key: [Word; 4],
+        /// The new balance.
+        value: Option<Word>,
+    },
+    /// Sets the nonce of an account.
+    Nonce {
+        /// The hashed key of the account.
+        key: [Word; 4],
+        /// The new nonce.
+        value: Option<Word>,
+    },
+    /// Sets the hashed token name.
+    TokenName(Option<[Word; 4]>),
+    /// Sets the hashed token symbol.
+    TokenSymbol(Option<[Word; 4]>),
+    /// Sets the number of decimals.
+    Decimals(Option<Word>),
+    /// A mutation to a key that is not part of the token storage.
+    Unknown(Mutation),
+}
+
+/// Decodes every `SolutionData` in the solution.
+///
+/// Only solutions to the token contract deployed at `token` are decoded,
+/// any other predicate is [`Decoded::Unknown`].
+pub fn solution(solution: &Solution, token: &ContentAddress) -> anyhow::Result<Vec<Decoded>> {
+    solution
+        .data
+        .iter()
+        .map(|data| solution_data(data, token))
+        .collect()
+}
+
+/// Decodes a single `SolutionData` to the token contract deployed at `token`.
+pub fn solution_data(data: &SolutionData, token: &ContentAddress) -> anyhow::Result<Decoded> {
+    let predicate = &data.predicate_to_solve;
+    let is = |abi| *predicate == on_token(token, abi);
+    let decoded = if is(super::token::Mint::ADDRESS) {
+        Decoded::Mint(mint(data)?)
+    } else if is(super::token::Burn::ADDRESS) {
+        Decoded::Burn(burn(data)?)
+    } else if is(super::token::Transfer::ADDRESS) {
+        Decoded::Transfer(transfer(data)?)
+    } else if is(super::token::Cancel::ADDRESS) {
+        Decoded::Cancel(cancel(data)?)
+    } else {
+        Decoded::Unknown(predicate.clone())
+    };
+    Ok(decoded)
+}
+
+/// Decodes a mint solution.
+fn mint(data: &SolutionData) -> anyhow::Result<Mint> {
+    let layout: Vec<Mutation> = super::token::Mint::PubVars {
+        key: [0; 4],
+        amount: 0,
+        decimals: 0,
+    }
+    .into();
+    let pub_vars = PubVars::new(&layout, &data.transient_data);
+    let mut vars = Reader::new(decision_variable(data)?);
+    Ok(Mint {
+        key: pub_vars.b256(0)?,
+        amount: pub_vars.int(1)?,
+        decimals: pub_vars.int(2)?,
+        auth: auth(&mut vars)?,
+        mutations: mutations(&data.state_mutations),
+    })
+}
+
+/// Decodes a burn solution.
+fn burn(data: &SolutionData) -> anyhow::Result<Burn> {
+    let layout: Vec<Mutation> = super::token::Burn::PubVars {
+        key: [0; 4],
+        amount: 0,
+    }
+    .into();
+    let pub_vars = PubVars::new(&layout, &data.transient_data);
+    let mut vars = Reader::new(decision_variable(data)?);
+    Ok(Burn {
+        key: pub_vars.b256(0)?,
+        amount: pub_vars.int(1)?,
+        auth: auth(&mut vars)?,
+        mutations: mutations(&data.state_mutations),
+    })
+}
+
+/// Decodes a transfer solution.
+fn transfer(data: &SolutionData) -> anyhow::Result<Transfer> {
+    let layout: Vec<Mutation> = super::token::Transfer::PubVars {
+        key: [0; 4],
+        to: [0; 4],
+        amount: 0,
+    }
+    .into();
+    let pub_vars = PubVars::new(&layout, &data.transient_data);
+    let mut vars = Reader::new(decision_variable(data)?);
+    Ok(Transfer {
+        key: pub_vars.b256(0)?,
+        to: pub_vars.b256(1)?,
+        amount: pub_vars.int(2)?,
+        auth: transfer_auth(&mut vars)?,
+        extra: extra_constraints(&mut vars)?,
+        mutations: mutations(&data.state_mutations),
+    })
+}
+
+/// Decodes a cancel solution.
+fn cancel(data: &SolutionData) -> anyhow::Result<Cancel> {
+    let layout: Vec<Mutation> = super::token::Cancel::PubVars { key: [0; 4] }.into();
+    let pub_vars = PubVars::new(&layout, &data.transient_data);
+    let mut vars = Reader::new(decision_variable(data)?);
+    Ok(Cancel {
+        key: pub_vars.b256(0)?,
+        auth: auth(&mut vars)?,
+        mutations: mutations(&data.state_mutations),
+    })
+}
+
+/// Returns the single `auth` decision variable of a token predicate.
+fn decision_variable(data: &SolutionData) -> anyhow::Result<&[Word]> {
+    match &data.decision_variables[..] {
+        [auth] => Ok(auth),
+        vars => bail!("Expected a single decision variable, got: {}", vars.len()),
+    }
+}
+
+/// Decodes a `BurnAuth`, `MintAuth` or `CancelAuth`.
+fn auth(reader: &mut Reader) -> anyhow::Result<Auth> {
+    let mut union = reader.union(AUTH_WORDS)?;
+    let auth = match union.word()? {
+        0 => Auth::Signed(signature(&mut union)?),
+        1 => Auth::Predicate(predicate_address(&mut union)?),
+        tag => bail!("Invalid auth tag: {}", tag),
+    };
+    Ok(auth)
+}
+
+/// Decodes a `TransferAuthMode`.
+fn transfer_auth(reader: &mut Reader) -> anyhow::Result<TransferAuth> {
+    let mut union = reader.union(TRANSFER_AUTH_MODE_WORDS)?;
+    let auth = match union.word()? {
+        0 => {
+            let signature = signature(&mut union)?;
+            let mode = match union.word()? {
+                0 => SignedMode::All,
+                1 => SignedMode::Key,
+                2 => SignedMode::KeyTo,
+                3 => SignedMode::KeyAmount,
+                tag => bail!("Invalid signed mode tag: {}", tag),
+            };
+            TransferAuth::Signed { signature, mode }
+        }
+        1 => TransferAuth::Predicate(predicate_address(&mut union)?),
+        tag => bail!("Invalid transfer auth tag: {}", tag),
+    };
+    Ok(auth)
+}
+
+/// Decodes an `ExtraConstraints`.
+fn extra_constraints(reader: &mut Reader) -> anyhow::Result<ExtraConstraints> {
+    let mut union = reader.union(EXTRA_CONSTRAINTS_WORDS)?;
+    let extra = match union.word()? {
+        0 => ExtraConstraints::Extra {
+            addr: predicate_address(&mut union)?,
+            path: union.word()?,
+        },
+        1 => ExtraConstraints::None,
+        tag => bail!("Invalid extra constraints tag: {}", tag),
+    };
+    Ok(extra)
+}
+
+/// Decodes a `Secp256k1Signature`.
+fn signature(reader: &mut Reader) -> anyhow::Result<Signature> {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&u8_32_from_word_4(reader.b256()?));
+    bytes[32..].copy_from_slice(&u8_32_from_word_4(reader.b256()?));
+    let id = reader.word()?;
+    let id = u8::try_from(id).map_err(|_| anyhow::anyhow!("Invalid recovery id: {}", id))?;
+    Ok(Signature(bytes, id))
+}
+
+/// Decodes a `PredicateAddress`.
+fn predicate_address(reader: &mut Reader) -> anyhow::Result<PredicateAddress> {
+    Ok(PredicateAddress {
+        contract: ContentAddress(u8_32_from_word_4(reader.b256()?)),
+        predicate: ContentAddress(u8_32_from_word_4(reader.b256()?)),
+    })
+}
+
+/// Decodes the state mutations of a token solution.
+fn mutations(mutations: &[Mutation]) -> Vec<StateMutation> {
+    mutations.iter().map(state_mutation).collect()
+}
+
+/// Decodes a single state mutation by matching its key against the token storage layout.
+fn state_mutation(mutation: &Mutation) -> StateMutation {
+    let Mutation { key, value } = mutation;
+    if let Some(account) = map_entry(key, &crate::balance_key([0; 4])) {
+        if let Ok(value) = optional_word(value) {
+            return StateMutation::Balance {
+                key: account,
+                value,
+            };
+        }
+    }
+    if let Some(account) = map_entry(key, &crate::nonce_key([0; 4])) {
+        if let Ok(value) = optional_word(value) {
+            return StateMutation::Nonce {
+                key: account,
+                value,
+            };
+        }
+    }
+    let storage = super::token::storage::mutations;
+    if *key == first_key(storage().token_name([0; 4]).into()) {
+        if let Ok(value) = optional_b256(value) {
+            return StateMutation::TokenName(value);
+        }
+    }
+    if *key == first_key(storage().token_symbol([0; 4]).into()) {
+        if let Ok(value) = optional_b256(value) {
+            return StateMutation::TokenSymbol(value);
+        }
+    }
+    if *key == first_key(storage().decimals(0).into()) {
+        if let Ok(value) = optional_word(value) {
+            return StateMutation::Decimals(value);
+        }
+    }
+    StateMutation::Unknown(mutation.clone())
+}
+
+/// Returns the key of the first mutation.
+fn first_key(mutations: Vec<Mutation>) -> Key {
+    mutations
+        .into_iter()
+        .next()
+        .expect("Must be a mutation")
+        .key
+}
+
+/// If `key` is an entry in the same storage map as `template`,
+/// returns the `b256` map key.
+///
+/// The `template` must be a key for the map entry `[0; 4]`.
+fn map_entry(key: &Key, template: &Key) -> Option<[Word; 4]> {
+    let prefix = template.len().checked_sub(4)?;
+    if key.len() != template.len() || key[..prefix] != template[..prefix] {
+        return None;
+    }
+    key[prefix..].try_into().ok()
+}
+
+/// Decodes a value that is either nil or a single word.
+pub(crate) fn optional_word(value: &[Word]) -> anyhow::Result<Option<Word>> {
+    match value {
+        [] => Ok(None),
+        [word] => Ok(Some(*word)),
+        _ => bail!("Expected single word, got: {:?}", value),
+    }
+}
+
+/// Decodes a value that is either nil or a `b256`.
+fn optional_b256(value: &[Word]) -> anyhow::Result<Option<[Word; 4]>> {
+    match value {
+        [] => Ok(None),
+        _ => Ok(Some(value.try_into().map_err(|_| {
+            anyhow::anyhow!("Expected four words, got: {:?}", value)
+        })?)),
+    }
+}
+
+/// Looks up public variables in the transient data.
+///
+/// The keys are taken from the ABI generated `PubVars` of the predicate
+/// so they stay in step with the contract.
+struct PubVars<'a> {
+    layout: &'a [Mutation],
+    transient_data: &'a [Mutation],
+}
+
+impl<'a> PubVars<'a> {
+    fn new(layout: &'a [Mutation], transient_data: &'a [Mutation]) -> Self {
+        Self {
+            layout,
+            transient_data,
+        }
+    }
+
+    /// Returns the value of the public variable at `ix`.
+    fn value(&self, ix: usize) -> anyhow::Result<&'a [Word]> {
+        let key = &self
+            .layout
+            .get(ix)
+            .ok_or_else(|| anyhow::anyhow!("No public variable at index {}", ix))?
+            .key;
+        self.transient_data
+            .iter()
+            .find(|m| m.key == *key)
+            .map(|m| &m.value[..])
+            .ok_or_else(|| anyhow::anyhow!("Missing public variable: {:?}", key))
+    }
+
+    fn int(&self, ix: usize) -> anyhow::Result<Word> {
+        match self.value(ix)? {
+            [word] => Ok(*word),
+            value => bail!("Expected single word, got: {:?}", value),
+        }
+    }
+
+    fn b256(&self, ix: usize) -> anyhow::Result<[Word; 4]> {
+        let value = self.value(ix)?;
+        value
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Expected four words, got: {:?}", value))
+    }
+}
+
+/// Reads words off the front of an encoded value.
+struct Reader<'a>(&'a [Word]);
+
+impl<'a> Reader<'a> {
+    fn new(words: &'a [Word]) -> Self {
+        Self(words)
+    }
+
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [Word]> {
+        ensure!(
+            self.0.len() >= n,
+            "Expected {} more words, got: {}",
+            n,
+            self.0.len()
+        );
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn word(&mut self) -> anyhow::Result<Word> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn b256(&mut self) -> anyhow::Result<[Word; 4]> {
+        Ok(self.take(4)?.try_into().expect("Must be four words"))
+    }
+
+    /// Takes a padded union of `size` words and returns a reader over it.
+    fn union(&mut self, size: usize) -> anyhow::Result<Reader<'a>> {
+        Ok(Reader(self.take(size)?))
+    }
+}
@@ -0,0 +1,66 @@
+//! # Error
+//! Token operations that break a rule of the contract.
+
+use essential_types::Word;
+
+use crate::check::Slot;
+
+/// A token operation that the contract would reject.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The amount is not positive (`constraint amount > 0`).
+    NonPositiveAmount(Word),
+    /// The sender and recipient of a transfer are the same key.
+    SelfTransfer,
+    /// The account has already been minted to (`@init_once(nonce; 1)`).
+    AlreadyMinted,
+    /// The new nonce is not the one the contract expects.
+    UnexpectedNonce {
+        /// The nonce the contract expects.
+        expected: Word,
+        /// The nonce that was given.
+        got: Word,
+    },
+    /// The balance is less than the amount (`constraint balance' >= 0`).
+    InsufficientBalance {
+        /// The current balance.
+        balance: Word,
+        /// The amount being removed.
+        amount: Word,
+    },
+    /// The new balance does not fit in a word.
+    BalanceOverflow,
+    /// More than one mutation of a solution sets the same slot.
+    ConflictingMutations(Slot),
+    /// A constraint of the predicate is not satisfied by a solution.
+    Unsatisfied(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NonPositiveAmount(amount) => {
+                write!(f, "Amount must be greater than zero, got: {}", amount)
+            }
+            Error::SelfTransfer => write!(f, "Cannot transfer to the sending key"),
+            Error::AlreadyMinted => write!(f, "Token has already been minted"),
+            Error::UnexpectedNonce { expected, got } => {
+                write!(f, "Expected new nonce {}, got: {}", expected, got)
+            }
+            Error::InsufficientBalance { balance, amount } => write!(
+                f,
+                "Insufficient balance: {} is less than amount {}",
+                balance, amount
+            ),
+            Error::BalanceOverflow => write!(f, "Balance overflow"),
+            Error::ConflictingMutations(slot) => {
+                write!(f, "More than one mutation sets storage::{}", slot)
+            }
+            Error::Unsatisfied(constraint) => {
+                write!(f, "Solution does not satisfy `constraint {}`", constraint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -3,7 +3,7 @@
 //! Taken contract front end implementation
 
 use anyhow::bail;
-use essential_types::{Key, Value, Word};
+use essential_types::{ContentAddress, Key, PredicateAddress, Value, Word};
 
 /// Module containing the token contract ABI.
 #[allow(missing_docs)]
@@ -15,10 +15,30 @@ pub mod token {
 }
 
 pub mod burn;
+pub mod check;
+pub mod decode;
+pub mod error;
 pub mod mint;
+mod rules;
+pub mod signer;
 pub mod transfer;
+pub mod verify;
+
+pub use error::Error;
+
+/// Returns the address of a predicate of the token contract deployed at `token`.
+///
+/// The ABI has the addresses of the compiled contract. Tokens deployed with
+/// another salt share its predicates but have their own contract address.
+pub(crate) fn on_token(token: &ContentAddress, abi: PredicateAddress) -> PredicateAddress {
+    PredicateAddress {
+        contract: token.clone(),
+        predicate: abi.predicate,
+    }
+}
 
 /// Represents a query result, which may or may not contain a value.
+#[derive(Clone, Debug)]
 pub struct Query(pub Option<Value>);
 
 /// Generates the key for querying an account's balance.
@@ -37,6 +57,15 @@ pub fn nonce_key(hashed_key: [Word; 4]) -> Key {
     keys.into_iter().next().expect("Must be a key")
 }
 
+/// Generates the key for querying the token's decimals.
+///
+/// The decimals are set by the first mint, so this is also the key to check
+/// that a token is live.
+pub fn decimals_key() -> Key {
+    let keys: Vec<_> = token::storage::keys::keys().decimals().into();
+    keys.into_iter().next().expect("Must be a key")
+}
+
 /// Extracts the nonce from a Query result.
 pub fn nonce(nonce: Query) -> anyhow::Result<Word> {
     let r = match nonce.0 {
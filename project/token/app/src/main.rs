@@ -1,16 +1,18 @@
 //! Command-line interface for interacting with the token contract.
 
-use anyhow::bail;
 use clap::{Args, Parser, Subcommand};
 use essential_app_utils::compile::compile_pint_project;
 use essential_rest_client::{
     builder_client::EssentialBuilderClient, node_client::EssentialNodeClient,
 };
-use essential_signer::Signature;
-use essential_types::{convert::word_4_from_u8_32, ContentAddress, PredicateAddress, Word};
-use essential_wallet::Wallet;
+use essential_types::{
+    convert::word_4_from_u8_32, solution::Solution, ContentAddress, PredicateAddress, Word,
+};
 use std::path::PathBuf;
-use token::Query;
+use token::{
+    signer::{Signer, SignerKind},
+    Query,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -19,14 +21,22 @@ struct Cli {
     /// If not set then a sensible default will be used (like ~/.essential-wallet).
     #[arg(short, long)]
     wallet: Option<PathBuf>,
+    /// Where the signing key is held.
+    /// One of `wallet`, `key` (prompts for a hex encoded secret key)
+    /// or, on unix, `remote:<socket path>` for a signing service.
+    #[arg(short, long, default_value = "wallet")]
+    signer: SignerKind,
+    /// The wallet account to sign with.
+    /// Only used by, and required for, the `wallet` signer.
+    // Its own id, as `external-balance` takes a hashed key named `account`.
+    #[arg(id = "wallet_account", short = 'a', long = "account", global = true)]
+    account: Option<String>,
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Args)]
 struct Mint {
-    /// The account to mint from.
-    account: String,
     /// The amount of token to mint.
     amount: Word,
     /// The name of the token.
@@ -43,8 +53,6 @@ struct Mint {
 
 #[derive(Args)]
 struct Transfer {
-    /// The account to transfer from.
-    from_account: String,
     /// The account to transfer to.
     /// Hashed key as hex.
     to_account: String,
@@ -60,8 +68,6 @@ struct Transfer {
 
 #[derive(Args)]
 struct Burn {
-    /// The account to burn from.
-    account: String,
     /// The amount of token to mint.
     amount: Word,
     /// The address of the node to connect to.
@@ -74,8 +80,6 @@ struct Burn {
 
 #[derive(Args)]
 struct Balance {
-    /// The account name to get the balance of.
-    account: String,
     /// The address of the node to connect to.
     node_api: String,
     /// The directory of the pint token contract.
@@ -93,6 +97,14 @@ struct ExternalBalance {
     pint_directory: PathBuf,
 }
 
+#[derive(Args)]
+struct Decode {
+    /// Path to a JSON encoded solution.
+    solution: PathBuf,
+    /// The directory of the pint token contract.
+    pint_directory: PathBuf,
+}
+
 #[derive(Subcommand)]
 enum Command {
     Mint(Mint),
@@ -100,6 +112,7 @@ enum Command {
     Transfer(Transfer),
     Balance(Balance),
     ExternalBalance(ExternalBalance),
+    Decode(Decode),
 }
 
 #[tokio::main]
@@ -111,52 +124,44 @@ async fn main() {
 }
 
 async fn run(cli: Cli) -> anyhow::Result<()> {
-    let Cli { wallet, command } = cli;
-    let wallet = match &command {
-        Command::ExternalBalance(_) => None,
-        _ => {
-            let pass = rpassword::prompt_password("Enter password to unlock wallet: ")?;
-            let wallet = match wallet {
-                Some(path) => essential_wallet::Wallet::new(&pass, path)?,
-                None => essential_wallet::Wallet::with_default_path(&pass)?,
-            };
-            Some(wallet)
-        }
-    };
+    let Cli {
+        wallet,
+        signer,
+        account,
+        command,
+    } = cli;
     match command {
         Command::Mint(args) => {
             println!(
-                "minting {} for account: {}, token name: {}, token symbol: {}",
-                args.amount, args.account, args.token_name, args.token_symbol
+                "minting {}, token name: {}, token symbol: {}",
+                args.amount, args.token_name, args.token_symbol
             );
-            let wallet = wallet.unwrap();
-            let addr = mint(wallet, args).await?;
+            let mut signer = signer.open(wallet, account)?;
+            let addr = mint(signer.as_mut(), args).await?;
             println!("sent mint solution: {}", addr);
         }
         Command::Burn(args) => {
-            println!("burning {} for account: {}", args.amount, args.account);
-            let wallet = wallet.unwrap();
-            let addr = burn(wallet, args).await?;
+            println!("burning {}", args.amount);
+            let mut signer = signer.open(wallet, account)?;
+            let addr = burn(signer.as_mut(), args).await?;
             println!("sent burn solution: {}", addr);
         }
         Command::Transfer(args) => {
             println!(
-                "transferring {} from account: {} to account: {}",
-                args.amount, args.from_account, args.to_account
+                "transferring {} to account: {}",
+                args.amount, args.to_account
             );
-            let wallet = wallet.unwrap();
-            let addr = transfer(wallet, args).await?;
+            let mut signer = signer.open(wallet, account)?;
+            let addr = transfer(signer.as_mut(), args).await?;
             println!("sent transfer solution: {}", addr);
         }
         Command::Balance(args) => {
             let Balance {
-                account,
                 node_api,
                 pint_directory,
             } = args;
-            println!("getting balance for account: {}", account);
-            let mut wallet = wallet.unwrap();
-            let hashed_key = hash_key(&mut wallet, &account);
+            let hashed_key = signer.open(wallet, account)?.hashed_key()?;
+            println!("getting balance for key: {:?}", hashed_key);
             let balance = get_balance(hashed_key, node_api, pint_directory).await?;
             println!("balance is {}", balance);
         }
@@ -175,23 +180,24 @@ async fn run(cli: Cli) -> anyhow::Result<()> {
             let balance = get_balance(hashed_key, node_api, pint_directory).await?;
             println!("balance is {}", balance);
         }
+        Command::Decode(args) => {
+            let Decode {
+                solution,
+                pint_directory,
+            } = args;
+            println!("decoding solution: {}", solution.display());
+            let solution: Solution = serde_json::from_str(&std::fs::read_to_string(solution)?)?;
+            let address = compile_address(pint_directory).await?;
+            for decoded in token::decode::solution(&solution, &address.contract)? {
+                println!("{:#?}", decoded);
+            }
+        }
     }
     Ok(())
 }
 
-/// Hashes the public key for an account.
-fn hash_key(wallet: &mut Wallet, account_name: &str) -> [Word; 4] {
-    let public_key = wallet.get_public_key(account_name).unwrap();
-    let essential_signer::PublicKey::Secp256k1(public_key) = public_key else {
-        panic!("Invalid public key")
-    };
-    let encoded = essential_sign::encode::public_key(&public_key);
-    word_4_from_u8_32(essential_hash::hash_words(&encoded))
-}
-
-async fn mint(mut wallet: Wallet, args: Mint) -> anyhow::Result<ContentAddress> {
+async fn mint(signer: &mut dyn Signer, args: Mint) -> anyhow::Result<ContentAddress> {
     let Mint {
-        account,
         amount,
         token_name,
         token_symbol,
@@ -200,7 +206,7 @@ async fn mint(mut wallet: Wallet, args: Mint) -> anyhow::Result<ContentAddress>
         pint_directory,
     } = args;
     let address = compile_address(pint_directory).await?;
-    let hashed_key = hash_key(&mut wallet, &account);
+    let hashed_key = signer.hashed_key()?;
     let node = EssentialNodeClient::new(node_api)?;
     let builder = EssentialBuilderClient::new(builder_api)?;
 
@@ -215,15 +221,13 @@ async fn mint(mut wallet: Wallet, args: Mint) -> anyhow::Result<ContentAddress>
         nonce: token::Query(nonce),
     };
     let to_sign = token::mint::data_to_sign(init)?;
-    let sig = wallet.sign_words(&to_sign.to_words(), &account)?;
-    let Signature::Secp256k1(sig) = sig else {
-        bail!("Invalid signature")
-    };
+    let sig = signer.sign_words(&to_sign.to_words())?;
     let balance_key = token::balance_key(hashed_key);
     let balance = node
         .query_state(address.contract.clone(), balance_key)
         .await?;
     let build_solution = token::mint::BuildSolution {
+        token: address.contract,
         new_nonce: to_sign.new_nonce,
         current_balance: Query(balance),
         hashed_key,
@@ -238,16 +242,15 @@ async fn mint(mut wallet: Wallet, args: Mint) -> anyhow::Result<ContentAddress>
     Ok(ca)
 }
 
-async fn burn(mut wallet: Wallet, args: Burn) -> anyhow::Result<ContentAddress> {
+async fn burn(signer: &mut dyn Signer, args: Burn) -> anyhow::Result<ContentAddress> {
     let Burn {
-        account,
         amount,
         node_api,
         builder_api,
         pint_directory,
     } = args;
     let address = compile_address(pint_directory).await?;
-    let hashed_key = hash_key(&mut wallet, &account);
+    let hashed_key = signer.hashed_key()?;
     let node = EssentialNodeClient::new(node_api)?;
     let builder = EssentialBuilderClient::new(builder_api)?;
 
@@ -258,19 +261,18 @@ async fn burn(mut wallet: Wallet, args: Burn) -> anyhow::Result<ContentAddress>
     let init = token::burn::Init {
         hashed_key,
         amount,
-        nonce: token::Query(nonce),
+        nonce: token::Query(nonce.clone()),
     };
     let to_sign = token::burn::data_to_sign(init)?;
-    let sig = wallet.sign_words(&to_sign.to_words(), &account)?;
-    let Signature::Secp256k1(sig) = sig else {
-        bail!("Invalid signature")
-    };
+    let sig = signer.sign_words(&to_sign.to_words())?;
     let balance_key = token::balance_key(hashed_key);
     let balance = node
         .query_state(address.contract.clone(), balance_key)
         .await?;
     let build_solution = token::burn::BuildSolution {
+        token: address.contract,
         new_nonce: to_sign.new_nonce,
+        current_nonce: Query(nonce),
         current_balance: Query(balance),
         hashed_key,
         amount: to_sign.amount,
@@ -281,17 +283,16 @@ async fn burn(mut wallet: Wallet, args: Burn) -> anyhow::Result<ContentAddress>
     Ok(ca)
 }
 
-async fn transfer(mut wallet: Wallet, args: Transfer) -> anyhow::Result<ContentAddress> {
+async fn transfer(signer: &mut dyn Signer, args: Transfer) -> anyhow::Result<ContentAddress> {
     let Transfer {
         amount,
         node_api,
         builder_api,
         pint_directory,
-        from_account,
         to_account,
     } = args;
     let address = compile_address(pint_directory).await?;
-    let hashed_from_key = hash_key(&mut wallet, &from_account);
+    let hashed_from_key = signer.hashed_key()?;
     let hashed_to_key = word_4_from_u8_32(
         hex::decode(to_account)?
             .try_into()
@@ -306,15 +307,12 @@ async fn transfer(mut wallet: Wallet, args: Transfer) -> anyhow::Result<ContentA
         .await?;
     let init = token::transfer::Init {
         amount,
-        nonce: token::Query(nonce),
+        nonce: token::Query(nonce.clone()),
         hashed_from_key,
         hashed_to_key,
     };
     let to_sign = token::transfer::data_to_sign(init)?;
-    let sig = wallet.sign_words(&to_sign.to_words(), &from_account)?;
-    let Signature::Secp256k1(sig) = sig else {
-        bail!("Invalid signature")
-    };
+    let sig = signer.sign_words(&to_sign.to_words())?;
     let balance_key = token::balance_key(hashed_from_key);
     let from_balance = node
         .query_state(address.contract.clone(), balance_key)
@@ -324,7 +322,9 @@ async fn transfer(mut wallet: Wallet, args: Transfer) -> anyhow::Result<ContentA
         .query_state(address.contract.clone(), balance_key)
         .await?;
     let build_solution = token::transfer::BuildSolution {
+        token: address.contract,
         new_nonce: to_sign.new_nonce,
+        current_nonce: Query(nonce),
         current_from_balance: Query(from_balance),
         current_to_balance: Query(to_balance),
         hashed_from_key,
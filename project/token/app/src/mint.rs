@@ -6,10 +6,16 @@ use essential_sign::secp256k1::ecdsa::RecoverableSignature;
 use essential_types::{
     convert::word_4_from_u8_32,
     solution::{Solution, SolutionData},
-    Word,
+    ContentAddress, Word,
 };
 
-use crate::{balance, nonce, Query};
+use crate::{
+    balance,
+    check::{PreState, Slot},
+    nonce, on_token,
+    rules::credit,
+    Error, Query,
+};
 
 /// Represents the initial data required for minting tokens.
 pub struct Init {
@@ -37,6 +43,8 @@ pub struct ToSign {
 
 /// Contains all necessary information to build a mint solution.
 pub struct BuildSolution {
+    /// The address of the token contract.
+    pub token: ContentAddress,
     /// The new nonce of the account.
     pub new_nonce: Word,
     /// The current balance of the account.
@@ -71,6 +79,8 @@ impl ToSign {
 }
 
 /// Prepares the data to be signed for a mint transaction.
+///
+/// Fails with [`Error::AlreadyMinted`] if the account already has a nonce.
 pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
     let Init {
         hashed_key,
@@ -78,7 +88,11 @@ pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
         amount,
         decimals,
     } = account;
-    let new_nonce = increment_nonce(nonce(current_nonce)?);
+    let current_nonce = nonce(current_nonce)?;
+    if current_nonce != 0 {
+        return Err(Error::AlreadyMinted.into());
+    }
+    let new_nonce = increment_nonce(current_nonce);
     Ok(ToSign {
         hashed_key,
         amount,
@@ -88,8 +102,12 @@ pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
 }
 
 /// Builds a mint solution based on the provided data.
+///
+/// Fails with [`Error::AlreadyMinted`] if the account's state is already initialized,
+/// or [`Error::UnexpectedNonce`] if the new nonce is not 1.
 pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
     let BuildSolution {
+        token,
         new_nonce,
         current_balance,
         hashed_key,
@@ -99,7 +117,26 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
         token_name,
         token_symbol,
     } = build;
-    let balance = calculate_new_balance(balance(current_balance)?, amount)?;
+    let to_sign = ToSign {
+        hashed_key,
+        amount,
+        decimals,
+        new_nonce,
+    };
+    crate::verify::mint(&to_sign, &signature)?;
+    if matches!(&current_balance.0, Some(b) if !b.is_empty()) {
+        return Err(Error::AlreadyMinted.into());
+    }
+    if new_nonce != 1 {
+        return Err(Error::UnexpectedNonce {
+            expected: 1,
+            got: new_nonce,
+        }
+        .into());
+    }
+    // A mint initializes all state so the nonce must be nil.
+    let pre = PreState::default().with(Slot::Balance(hashed_key), &current_balance);
+    let balance = credit(balance(current_balance)?, amount)?;
     let pub_vars = super::token::Mint::PubVars {
         key: hashed_key,
         amount,
@@ -116,11 +153,12 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
         .decimals(decimals)
         .nonce(|nonces| nonces.entry(hashed_key, new_nonce));
     let solution = SolutionData {
-        predicate_to_solve: super::token::Mint::ADDRESS,
+        predicate_to_solve: on_token(&token, super::token::Mint::ADDRESS),
         decision_variables: vars.into(),
         transient_data: pub_vars.into(),
         state_mutations: mutations.into(),
     };
+    crate::check::solution_data(&pre, &token, &solution)?;
     Ok(Solution {
         data: vec![solution],
     })
@@ -130,10 +168,3 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
 fn increment_nonce(nonce: Word) -> Word {
     nonce + 1
 }
-
-/// Calculates the new balance after minting tokens.
-fn calculate_new_balance(balance: Word, amount: Word) -> anyhow::Result<Word> {
-    balance
-        .checked_add(amount)
-        .ok_or(anyhow::anyhow!("Insufficient balance"))
-}
@@ -0,0 +1,38 @@
+//! # Rules
+//! Rules of the token contract that are checked before signing or building a solution.
+
+use essential_types::Word;
+
+use crate::Error;
+
+/// Checks `constraint amount > 0`.
+pub(crate) fn positive_amount(amount: Word) -> Result<(), Error> {
+    if amount > 0 {
+        Ok(())
+    } else {
+        Err(Error::NonPositiveAmount(amount))
+    }
+}
+
+/// Checks that a transfer does not send to its own key.
+pub(crate) fn distinct_keys(from: [Word; 4], to: [Word; 4]) -> Result<(), Error> {
+    if from != to {
+        Ok(())
+    } else {
+        Err(Error::SelfTransfer)
+    }
+}
+
+/// Subtracts the amount from the balance.
+pub(crate) fn debit(balance: Word, amount: Word) -> Result<Word, Error> {
+    match balance.checked_sub(amount) {
+        Some(new_balance) if new_balance >= 0 => Ok(new_balance),
+        Some(_) => Err(Error::InsufficientBalance { balance, amount }),
+        None => Err(Error::BalanceOverflow),
+    }
+}
+
+/// Adds the amount to the balance.
+pub(crate) fn credit(balance: Word, amount: Word) -> Result<Word, Error> {
+    balance.checked_add(amount).ok_or(Error::BalanceOverflow)
+}
@@ -0,0 +1,252 @@
+//! # Signer
+//! Signs the word payloads of token solutions.
+//!
+//! The key may live in an essential wallet, in memory or, on unix, in a
+//! separate signing service reached over a local socket.
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::bail;
+#[cfg(unix)]
+use anyhow::{ensure, Context};
+#[cfg(unix)]
+use essential_sign::secp256k1::ecdsa::RecoveryId;
+use essential_sign::secp256k1::{
+    ecdsa::RecoverableSignature, Message, PublicKey, Secp256k1, SecretKey,
+};
+use essential_types::Word;
+use essential_wallet::Wallet;
+
+/// Something that can sign the data of a token solution.
+pub trait Signer {
+    /// The public key that signatures recover to.
+    fn public_key(&mut self) -> anyhow::Result<PublicKey>;
+
+    /// Signs the sha256 hash of the words, as checked by `@verify_key`.
+    fn sign_words(&mut self, words: &[Word]) -> anyhow::Result<RecoverableSignature>;
+
+    /// The hashed public key, which is the key of the account in the token contract.
+    fn hashed_key(&mut self) -> anyhow::Result<[Word; 4]> {
+        Ok(crate::verify::hash_public_key(&self.public_key()?))
+    }
+}
+
+/// Where the signing key is held.
+///
+/// Parses from `wallet`, `key` or, on unix, `remote:<socket path>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignerKind {
+    /// An account in an essential wallet.
+    Wallet,
+    /// A hex encoded secret key, prompted for when opened.
+    Key,
+    /// A signing service listening on a unix socket.
+    #[cfg(unix)]
+    Remote(PathBuf),
+}
+
+/// Signs with an account in an essential wallet.
+pub struct WalletSigner {
+    wallet: Wallet,
+    account: String,
+}
+
+/// Signs with a secp256k1 secret key held in memory.
+pub struct KeySigner(SecretKey);
+
+/// How long a [`RemoteSigner`] waits for the service to read a request or send a response.
+#[cfg(unix)]
+pub const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signs with a key held by a signing service listening on a unix socket.
+///
+/// Requests and responses are newline delimited JSON objects:
+/// - `{"method":"public_key"}` returns `{"public_key":"<33 byte compressed key as hex>"}`.
+/// - `{"method":"sign_hash","hash":"<32 bytes as hex>"}` returns
+///   `{"signature":"<64 byte compact signature as hex>","recovery_id":<0-3>}`.
+///
+/// Failures are returned as `{"error":"<message>"}`. A request also fails if
+/// the service takes longer than [`REMOTE_TIMEOUT`] to read it or respond.
+#[cfg(unix)]
+pub struct RemoteSigner {
+    socket: PathBuf,
+    public_key: Option<PublicKey>,
+}
+
+impl SignerKind {
+    /// Opens the signer.
+    ///
+    /// The wallet signer needs the account to sign with.
+    /// The other signers hold a single key, so giving them an account is an error.
+    pub fn open(
+        self,
+        wallet: Option<PathBuf>,
+        account: Option<String>,
+    ) -> anyhow::Result<Box<dyn Signer>> {
+        let signer: Box<dyn Signer> = match (self, account) {
+            (SignerKind::Wallet, None) => bail!("The wallet signer needs an account"),
+            (SignerKind::Wallet, Some(account)) => {
+                let pass = rpassword::prompt_password("Enter password to unlock wallet: ")?;
+                let wallet = match wallet {
+                    Some(path) => Wallet::new(&pass, path)?,
+                    None => Wallet::with_default_path(&pass)?,
+                };
+                Box::new(WalletSigner::new(wallet, account))
+            }
+            (_, Some(account)) => bail!(
+                "Only the wallet signer signs with an account, got account: {}",
+                account
+            ),
+            (SignerKind::Key, None) => {
+                let key = rpassword::prompt_password("Enter secret key as hex: ")?;
+                Box::new(KeySigner::new(SecretKey::from_slice(&hex::decode(
+                    key.trim(),
+                )?)?))
+            }
+            #[cfg(unix)]
+            (SignerKind::Remote(socket), None) => Box::new(RemoteSigner::new(socket)),
+        };
+        Ok(signer)
+    }
+}
+
+impl FromStr for SignerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wallet" => Ok(SignerKind::Wallet),
+            "key" => Ok(SignerKind::Key),
+            #[cfg(unix)]
+            _ if s.starts_with("remote:") => Ok(SignerKind::Remote(s["remote:".len()..].into())),
+            _ => bail!("Unknown signer: {}", s),
+        }
+    }
+}
+
+impl WalletSigner {
+    /// Signs with the named account in the wallet.
+    pub fn new(wallet: Wallet, account: impl Into<String>) -> Self {
+        Self {
+            wallet,
+            account: account.into(),
+        }
+    }
+}
+
+impl KeySigner {
+    /// Signs with the given secret key.
+    pub fn new(key: SecretKey) -> Self {
+        Self(key)
+    }
+}
+
+#[cfg(unix)]
+impl RemoteSigner {
+    /// Signs with the service listening on the socket.
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+            public_key: None,
+        }
+    }
+
+    /// Sends a single request and waits for the response.
+    fn request(&self, request: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .with_context(|| format!("Failed to connect to signer at {}", self.socket.display()))?;
+        stream.set_read_timeout(Some(REMOTE_TIMEOUT))?;
+        stream.set_write_timeout(Some(REMOTE_TIMEOUT))?;
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+        let response: serde_json::Value = serde_json::from_str(&response)?;
+        if let Some(error) = response.get("error") {
+            bail!("Signer returned an error: {}", error);
+        }
+        Ok(response)
+    }
+}
+
+impl Signer for WalletSigner {
+    fn public_key(&mut self) -> anyhow::Result<PublicKey> {
+        let essential_signer::PublicKey::Secp256k1(public_key) =
+            self.wallet.get_public_key(&self.account)?
+        else {
+            bail!("Invalid public key")
+        };
+        Ok(public_key)
+    }
+
+    fn sign_words(&mut self, words: &[Word]) -> anyhow::Result<RecoverableSignature> {
+        let essential_signer::Signature::Secp256k1(sig) =
+            self.wallet.sign_words(words, &self.account)?
+        else {
+            bail!("Invalid signature")
+        };
+        Ok(sig)
+    }
+}
+
+impl Signer for KeySigner {
+    fn public_key(&mut self) -> anyhow::Result<PublicKey> {
+        Ok(self.0.public_key(&Secp256k1::new()))
+    }
+
+    fn sign_words(&mut self, words: &[Word]) -> anyhow::Result<RecoverableSignature> {
+        let message = Message::from_digest(essential_hash::hash_words(words));
+        Ok(Secp256k1::new().sign_ecdsa_recoverable(&message, &self.0))
+    }
+}
+
+#[cfg(unix)]
+impl Signer for RemoteSigner {
+    fn public_key(&mut self) -> anyhow::Result<PublicKey> {
+        if let Some(public_key) = self.public_key {
+            return Ok(public_key);
+        }
+        let response = self.request(serde_json::json!({ "method": "public_key" }))?;
+        let public_key = response
+            .get("public_key")
+            .and_then(|k| k.as_str())
+            .context("Signer response is missing `public_key`")?;
+        let public_key = PublicKey::from_slice(&hex::decode(public_key)?)?;
+        self.public_key = Some(public_key);
+        Ok(public_key)
+    }
+
+    fn sign_words(&mut self, words: &[Word]) -> anyhow::Result<RecoverableSignature> {
+        let hash = essential_hash::hash_words(words);
+        let response = self.request(serde_json::json!({
+            "method": "sign_hash",
+            "hash": hex::encode(hash),
+        }))?;
+        let signature = response
+            .get("signature")
+            .and_then(|s| s.as_str())
+            .context("Signer response is missing `signature`")?;
+        let recovery_id = response
+            .get("recovery_id")
+            .and_then(|id| id.as_i64())
+            .context("Signer response is missing `recovery_id`")?;
+        let recovery_id = RecoveryId::from_i32(i32::try_from(recovery_id)?)?;
+        let sig = RecoverableSignature::from_compact(&hex::decode(signature)?, recovery_id)?;
+
+        // Make sure the service signed with the key it advertises.
+        let recovered =
+            Secp256k1::verification_only().recover_ecdsa(&Message::from_digest(hash), &sig)?;
+        ensure!(
+            recovered == self.public_key()?,
+            "Signer returned a signature from a different key"
+        );
+        Ok(sig)
+    }
+}
@@ -5,10 +5,16 @@ use essential_app_utils::inputs::Encode;
 use essential_sign::secp256k1::ecdsa::RecoverableSignature;
 use essential_types::{
     solution::{Solution, SolutionData},
-    Word,
+    ContentAddress, Word,
 };
 
-use crate::{balance, nonce, Query};
+use crate::{
+    balance,
+    check::{PreState, Slot},
+    nonce, on_token,
+    rules::{credit, debit, distinct_keys, positive_amount},
+    Query,
+};
 
 /// Represents the initial data required for transferring tokens.
 pub struct Init {
@@ -36,6 +42,8 @@ pub struct ToSign {
 
 /// Contains all necessary information to build a transfer solution.
 pub struct BuildSolution {
+    /// The address of the token contract.
+    pub token: ContentAddress,
     /// The hashed key of the sender.
     pub hashed_from_key: [Word; 4],
     /// The hashed key of the recipient.
@@ -44,6 +52,8 @@ pub struct BuildSolution {
     pub new_nonce: Word,
     /// The amount of tokens to transfer.
     pub amount: Word,
+    /// The current nonce of the sender, as queried before signing.
+    pub current_nonce: Query,
     /// The current balance of the sender.
     pub current_from_balance: Query,
     /// The current balance of the recipient.
@@ -71,6 +81,9 @@ impl ToSign {
 }
 
 /// Prepares the data to be signed for a transfer solution.
+///
+/// Fails with an [`Error`](crate::Error) if the amount is not positive
+/// or the sender and recipient are the same key.
 pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
     let Init {
         hashed_from_key,
@@ -78,6 +91,8 @@ pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
         amount,
         nonce: current_nonce,
     } = account;
+    positive_amount(amount)?;
+    distinct_keys(hashed_from_key, hashed_to_key)?;
     let new_nonce = increment_nonce(nonce(current_nonce)?);
     Ok(ToSign {
         amount,
@@ -88,18 +103,35 @@ pub fn data_to_sign(account: Init) -> anyhow::Result<ToSign> {
 }
 
 /// Builds a transfer solution based on the provided data.
+///
+/// Fails with an [`Error`](crate::Error) if the transfer breaks a rule of the contract.
 pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
     let BuildSolution {
+        token,
         hashed_from_key,
         hashed_to_key,
         new_nonce,
         amount,
+        current_nonce,
         current_from_balance,
         current_to_balance,
         signature,
     } = build;
-    let from_balance = calculate_from_balance(balance(current_from_balance)?, amount)?;
-    let to_balance = calculate_to_balance(balance(current_to_balance)?, amount)?;
+    let to_sign = ToSign {
+        hashed_from_key,
+        hashed_to_key,
+        amount,
+        new_nonce,
+    };
+    crate::verify::transfer(&to_sign, &signature)?;
+    positive_amount(amount)?;
+    distinct_keys(hashed_from_key, hashed_to_key)?;
+    let pre = PreState::default()
+        .with(Slot::Balance(hashed_from_key), &current_from_balance)
+        .with(Slot::Balance(hashed_to_key), &current_to_balance)
+        .with(Slot::Nonce(hashed_from_key), &current_nonce);
+    let from_balance = debit(balance(current_from_balance)?, amount)?;
+    let to_balance = credit(balance(current_to_balance)?, amount)?;
     let pub_vars = super::token::Transfer::PubVars {
         key: hashed_from_key,
         to: hashed_to_key,
@@ -116,11 +148,12 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
         .balances(|map| map.entry(hashed_to_key, to_balance))
         .nonce(|nonces| nonces.entry(hashed_from_key, new_nonce));
     let solution = SolutionData {
-        predicate_to_solve: super::token::Transfer::ADDRESS,
+        predicate_to_solve: on_token(&token, super::token::Transfer::ADDRESS),
         decision_variables: vars.into(),
         transient_data: pub_vars.into(),
         state_mutations: mutations.into(),
     };
+    crate::check::solution_data(&pre, &token, &solution)?;
     Ok(Solution {
         data: vec![solution],
     })
@@ -130,17 +163,3 @@ pub fn build_solution(build: BuildSolution) -> anyhow::Result<Solution> {
 fn increment_nonce(nonce: Word) -> Word {
     nonce + 1
 }
-
-/// Calculates the new balance for the sender after transferring tokens.
-fn calculate_from_balance(from_balance: Word, amount: Word) -> anyhow::Result<Word> {
-    from_balance
-        .checked_sub(amount)
-        .ok_or(anyhow::anyhow!("Insufficient balance"))
-}
-
-/// Calculates the new balance for the recipient after receiving tokens.
-fn calculate_to_balance(to_balance: Word, amount: Word) -> anyhow::Result<Word> {
-    to_balance
-        .checked_add(amount)
-        .ok_or(anyhow::anyhow!("Insufficient balance"))
-}
@@ -0,0 +1,52 @@
+//! # Verify
+//! Reproduces the contract's `@verify_key` check in Rust.
+//!
+//! The contract checks `__sha256(__recover_secp256k1(__sha256(data), sig)) == key`,
+//! so a signature over the wrong data, by the wrong key or with the wrong
+//! nonce is only rejected by the node. These functions run the same check locally.
+
+use anyhow::ensure;
+use essential_sign::secp256k1::{ecdsa::RecoverableSignature, Message, PublicKey, Secp256k1};
+use essential_types::{convert::word_4_from_u8_32, Word};
+
+use crate::{burn, mint, transfer};
+
+/// Checks that the signature over `data` was made by the key that hashes to `key`.
+pub fn verify_key(
+    data: &[Word],
+    signature: &RecoverableSignature,
+    key: [Word; 4],
+) -> anyhow::Result<()> {
+    let message = Message::from_digest(essential_hash::hash_words(data));
+    let public_key = Secp256k1::verification_only().recover_ecdsa(&message, signature)?;
+    ensure!(
+        hash_public_key(&public_key) == key,
+        "Signature does not match key {:?}, was the data or nonce changed after signing?",
+        key
+    );
+    Ok(())
+}
+
+/// Hashes a public key the same way the contract hashes a recovered key.
+pub fn hash_public_key(public_key: &PublicKey) -> [Word; 4] {
+    let encoded = essential_sign::encode::public_key(public_key);
+    word_4_from_u8_32(essential_hash::hash_words(&encoded))
+}
+
+/// Verifies the signature over a mint.
+pub fn mint(to_sign: &mint::ToSign, signature: &RecoverableSignature) -> anyhow::Result<()> {
+    verify_key(&to_sign.to_words(), signature, to_sign.hashed_key)
+}
+
+/// Verifies the signature over a burn.
+pub fn burn(to_sign: &burn::ToSign, signature: &RecoverableSignature) -> anyhow::Result<()> {
+    verify_key(&to_sign.to_words(), signature, to_sign.hashed_key)
+}
+
+/// Verifies the signature over a transfer.
+pub fn transfer(
+    to_sign: &transfer::ToSign,
+    signature: &RecoverableSignature,
+) -> anyhow::Result<()> {
+    verify_key(&to_sign.to_words(), signature, to_sign.hashed_from_key)
+}
@@ -0,0 +1,225 @@
+use essential_signer::Signature;
+use essential_types::{ContentAddress, PredicateAddress, Word};
+use essential_wallet::Wallet;
+use token::{
+    check::{PreState, Slot},
+    decode::{StateMutation, TransferAuth},
+    Error, Query,
+};
+
+/// The private key for the test account.
+const PRIV_KEY: &str = "128A3D2146A69581FD8FC4C0A9B7A96A5755D85255D4E47F814AFA69D7726C8D";
+
+#[test]
+fn signed_transfer_to_self_is_rejected() {
+    let (mut wallet, alice_hashed_key) = wallet_with_alice();
+
+    // Sign a transfer from Alice to Alice
+    let to_sign = token::transfer::ToSign {
+        hashed_from_key: alice_hashed_key,
+        hashed_to_key: alice_hashed_key,
+        amount: 500,
+        new_nonce: 1,
+    };
+    let Signature::Secp256k1(sig) = wallet.sign_words(&to_sign.to_words(), "alice").unwrap() else {
+        panic!("Invalid signature")
+    };
+
+    let build = token::transfer::BuildSolution {
+        token: token::token::ADDRESS,
+        hashed_from_key: alice_hashed_key,
+        hashed_to_key: alice_hashed_key,
+        new_nonce: to_sign.new_nonce,
+        amount: 500,
+        current_nonce: Query(None),
+        current_from_balance: Query(Some(vec![1000])),
+        current_to_balance: Query(Some(vec![1000])),
+        signature: sig,
+    };
+    let err = token::transfer::build_solution(build).unwrap_err();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::SelfTransfer));
+}
+
+#[test]
+fn transfer_to_self_has_conflicting_mutations() {
+    let alice = [1, 1, 1, 1];
+    let pre = PreState::default().with(Slot::Balance(alice), &Query(Some(vec![1000])));
+    let transfer = token::decode::Transfer {
+        key: alice,
+        to: alice,
+        amount: 500,
+        auth: TransferAuth::Predicate(PredicateAddress {
+            contract: ContentAddress([0; 32]),
+            predicate: ContentAddress([0; 32]),
+        }),
+        extra: token::decode::ExtraConstraints::None,
+        mutations: vec![
+            StateMutation::Balance {
+                key: alice,
+                value: Some(500),
+            },
+            StateMutation::Balance {
+                key: alice,
+                value: Some(1500),
+            },
+            StateMutation::Nonce {
+                key: alice,
+                value: Some(1),
+            },
+        ],
+    };
+    let err = token::check::transfer(&pre, &transfer).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::ConflictingMutations(Slot::Balance(alice)))
+    );
+}
+
+#[test]
+fn transfer_with_drifted_receiver_balance_is_unsatisfied() {
+    let alice = [1, 1, 1, 1];
+    let bob = [2, 2, 2, 2];
+    let pre = PreState::default()
+        .with(Slot::Balance(alice), &Query(Some(vec![1000])))
+        .with(Slot::Balance(bob), &Query(Some(vec![10])));
+    let transfer = |bob_balance| token::decode::Transfer {
+        key: alice,
+        to: bob,
+        amount: 500,
+        auth: TransferAuth::Predicate(PredicateAddress {
+            contract: ContentAddress([0; 32]),
+            predicate: ContentAddress([0; 32]),
+        }),
+        extra: token::decode::ExtraConstraints::None,
+        mutations: vec![
+            StateMutation::Balance {
+                key: alice,
+                value: Some(500),
+            },
+            StateMutation::Balance {
+                key: bob,
+                value: Some(bob_balance),
+            },
+            StateMutation::Nonce {
+                key: alice,
+                value: Some(1),
+            },
+        ],
+    };
+
+    token::check::transfer(&pre, &transfer(510)).unwrap();
+
+    // Bob's balance was set to the amount rather than incremented by it
+    let err = token::check::transfer(&pre, &transfer(500)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::Unsatisfied("@init_delta(receiver_balance; amount)"))
+    );
+}
+
+#[test]
+fn mint_must_use_the_contract_name_and_symbol() {
+    let (mut wallet, alice_hashed_key) = wallet_with_alice();
+    let build = |wallet: &mut Wallet, name: &str, symbol: &str| {
+        mint(wallet, "alice", alice_hashed_key, name, symbol)
+    };
+
+    token::mint::build_solution(build(&mut wallet, "alice coin", "ALC")).unwrap();
+
+    let err = token::mint::build_solution(build(&mut wallet, "bob coin", "ALC")).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::Unsatisfied("@init_once(token_name; config::NAME)"))
+    );
+    let err = token::mint::build_solution(build(&mut wallet, "alice coin", "BOB")).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::Unsatisfied(
+            "@init_once(token_symbol; config::SYMBOL)"
+        ))
+    );
+}
+
+#[test]
+fn mint_by_other_key_is_unsatisfied() {
+    let (mut wallet, _) = wallet_with_alice();
+    wallet
+        .insert_key(
+            "mallory",
+            essential_signer::Key::Secp256k1(
+                essential_signer::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+            ),
+        )
+        .unwrap();
+    let essential_signer::PublicKey::Secp256k1(public_key) =
+        wallet.get_public_key("mallory").unwrap()
+    else {
+        panic!("Invalid public key")
+    };
+    let mallory_hashed_key = token::verify::hash_public_key(&public_key);
+
+    // Mallory's signature is valid but she is not the minter
+    let build = mint(
+        &mut wallet,
+        "mallory",
+        mallory_hashed_key,
+        "alice coin",
+        "ALC",
+    );
+    let err = token::mint::build_solution(build).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::Unsatisfied("key == config::MINT_KEY"))
+    );
+}
+
+// Helper function to build a first mint signed by the account
+fn mint(
+    wallet: &mut Wallet,
+    account: &str,
+    hashed_key: [Word; 4],
+    token_name: &str,
+    token_symbol: &str,
+) -> token::mint::BuildSolution {
+    let to_sign = token::mint::ToSign {
+        hashed_key,
+        amount: 1000,
+        decimals: 18,
+        new_nonce: 1,
+    };
+    let Signature::Secp256k1(sig) = wallet.sign_words(&to_sign.to_words(), account).unwrap() else {
+        panic!("Invalid signature")
+    };
+    token::mint::BuildSolution {
+        token: token::token::ADDRESS,
+        new_nonce: to_sign.new_nonce,
+        current_balance: Query(None),
+        hashed_key,
+        amount: 1000,
+        decimals: 18,
+        signature: sig,
+        token_name: token_name.to_string(),
+        token_symbol: token_symbol.to_string(),
+    }
+}
+
+// Helper function to create a wallet holding Alice's key
+fn wallet_with_alice() -> (Wallet, [Word; 4]) {
+    let mut wallet = essential_wallet::Wallet::temp().unwrap();
+    let key = hex::decode(PRIV_KEY).unwrap();
+    wallet
+        .insert_key(
+            "alice",
+            essential_signer::Key::Secp256k1(
+                essential_signer::secp256k1::SecretKey::from_slice(&key).unwrap(),
+            ),
+        )
+        .unwrap();
+    let essential_signer::PublicKey::Secp256k1(public_key) =
+        wallet.get_public_key("alice").unwrap()
+    else {
+        panic!("Invalid public key")
+    };
+    let hashed_key = token::verify::hash_public_key(&public_key);
+    (wallet, hashed_key)
+}
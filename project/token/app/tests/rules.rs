@@ -0,0 +1,232 @@
+use essential_sign::secp256k1::{ecdsa::RecoverableSignature, SecretKey};
+use essential_types::{ContentAddress, Word};
+use token::{
+    decode::Decoded,
+    signer::{KeySigner, Signer},
+    Error, Query,
+};
+
+const BOB: [Word; 4] = [2, 2, 2, 2];
+
+#[test]
+fn transfer_rejects_zero_and_negative_amounts() {
+    for amount in [0, -1] {
+        let init = token::transfer::Init {
+            hashed_from_key: alice(),
+            hashed_to_key: BOB,
+            amount,
+            nonce: Query(None),
+        };
+        let err = token::transfer::data_to_sign(init).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::NonPositiveAmount(amount))
+        );
+
+        let build = transfer(alice(), BOB, amount, 1000);
+        let err = token::transfer::build_solution(build).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::NonPositiveAmount(amount))
+        );
+    }
+}
+
+#[test]
+fn transfer_rejects_self_transfer() {
+    let init = token::transfer::Init {
+        hashed_from_key: alice(),
+        hashed_to_key: alice(),
+        amount: 10,
+        nonce: Query(None),
+    };
+    let err = token::transfer::data_to_sign(init).err().unwrap();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::SelfTransfer));
+
+    let build = transfer(alice(), alice(), 10, 1000);
+    let err = token::transfer::build_solution(build).unwrap_err();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::SelfTransfer));
+}
+
+#[test]
+fn transfer_rejects_insufficient_balance() {
+    let build = transfer(alice(), BOB, 11, 10);
+    let err = token::transfer::build_solution(build).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::InsufficientBalance {
+            balance: 10,
+            amount: 11
+        })
+    );
+}
+
+#[test]
+fn burn_rejects_zero_and_negative_amounts() {
+    for amount in [0, -1] {
+        let init = token::burn::Init {
+            hashed_key: alice(),
+            amount,
+            nonce: Query(None),
+        };
+        let err = token::burn::data_to_sign(init).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::NonPositiveAmount(amount))
+        );
+
+        let build = burn(amount, Query(Some(vec![1000])));
+        let err = token::burn::build_solution(build).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::NonPositiveAmount(amount))
+        );
+    }
+}
+
+#[test]
+fn burn_rejects_insufficient_balance() {
+    let err = token::burn::build_solution(burn(1, Query(None))).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::InsufficientBalance {
+            balance: 0,
+            amount: 1
+        })
+    );
+}
+
+#[test]
+fn mint_rejects_second_mint() {
+    let init = token::mint::Init {
+        hashed_key: alice(),
+        amount: 10,
+        decimals: 18,
+        nonce: Query(Some(vec![1])),
+    };
+    let err = token::mint::data_to_sign(init).err().unwrap();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::AlreadyMinted));
+
+    let err = token::mint::build_solution(mint(1, Some(vec![10]))).unwrap_err();
+    assert_eq!(err.downcast_ref::<Error>(), Some(&Error::AlreadyMinted));
+}
+
+#[test]
+fn mint_rejects_nonces_other_than_one() {
+    let err = token::mint::build_solution(mint(2, None)).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<Error>(),
+        Some(&Error::UnexpectedNonce {
+            expected: 1,
+            got: 2
+        })
+    );
+}
+
+#[test]
+fn signatures_are_checked_before_rules() {
+    // A zero amount signed over other data fails on the signature
+    let mut build = transfer(alice(), BOB, 0, 1000);
+    build.signature = sign(&[0]);
+    let err = token::transfer::build_solution(build).unwrap_err();
+    assert!(err.downcast_ref::<Error>().is_none());
+}
+
+#[test]
+fn solutions_target_the_given_token() {
+    let other = ContentAddress([7; 32]);
+    let build = token::transfer::BuildSolution {
+        token: other.clone(),
+        ..transfer(alice(), BOB, 10, 1000)
+    };
+    let solution = token::transfer::build_solution(build).unwrap();
+    assert_eq!(solution.data[0].predicate_to_solve.contract, other);
+    assert!(matches!(
+        token::decode::solution(&solution, &other).unwrap()[..],
+        [Decoded::Transfer(_)]
+    ));
+    assert!(matches!(
+        token::decode::solution(&solution, &token::token::ADDRESS).unwrap()[..],
+        [Decoded::Unknown(_)]
+    ));
+}
+
+// Helper function to build a transfer signed by Alice
+fn transfer(
+    from: [Word; 4],
+    to: [Word; 4],
+    amount: Word,
+    from_balance: Word,
+) -> token::transfer::BuildSolution {
+    let to_sign = token::transfer::ToSign {
+        hashed_from_key: from,
+        hashed_to_key: to,
+        amount,
+        new_nonce: 1,
+    };
+    token::transfer::BuildSolution {
+        token: token::token::ADDRESS,
+        hashed_from_key: from,
+        hashed_to_key: to,
+        new_nonce: 1,
+        amount,
+        current_nonce: Query(None),
+        current_from_balance: Query(Some(vec![from_balance])),
+        current_to_balance: Query(None),
+        signature: sign(&to_sign.to_words()),
+    }
+}
+
+// Helper function to build a burn of Alice's tokens signed by Alice
+fn burn(amount: Word, current_balance: Query) -> token::burn::BuildSolution {
+    let to_sign = token::burn::ToSign {
+        hashed_key: alice(),
+        amount,
+        new_nonce: 1,
+    };
+    token::burn::BuildSolution {
+        token: token::token::ADDRESS,
+        new_nonce: 1,
+        current_nonce: Query(None),
+        current_balance,
+        hashed_key: alice(),
+        amount,
+        signature: sign(&to_sign.to_words()),
+    }
+}
+
+// Helper function to build a mint to Alice signed by Alice
+fn mint(new_nonce: Word, current_balance: Option<Vec<Word>>) -> token::mint::BuildSolution {
+    let to_sign = token::mint::ToSign {
+        hashed_key: alice(),
+        amount: 10,
+        decimals: 18,
+        new_nonce,
+    };
+    token::mint::BuildSolution {
+        token: token::token::ADDRESS,
+        new_nonce,
+        current_balance: Query(current_balance),
+        hashed_key: alice(),
+        amount: 10,
+        decimals: 18,
+        signature: sign(&to_sign.to_words()),
+        token_name: "alice coin".to_string(),
+        token_symbol: "ALC".to_string(),
+    }
+}
+
+// Helper function to get Alice's hashed key
+fn alice() -> [Word; 4] {
+    alice_signer().hashed_key().unwrap()
+}
+
+// Helper function to sign words as Alice
+fn sign(words: &[Word]) -> RecoverableSignature {
+    alice_signer().sign_words(words).unwrap()
+}
+
+// Helper function to create a signer holding Alice's key
+fn alice_signer() -> KeySigner {
+    KeySigner::new(SecretKey::from_slice(&[1; 32]).unwrap())
+}
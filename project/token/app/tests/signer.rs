@@ -0,0 +1,88 @@
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixListener,
+};
+
+#[cfg(unix)]
+use essential_sign::secp256k1::Secp256k1;
+use essential_sign::secp256k1::SecretKey;
+#[cfg(unix)]
+use token::signer::RemoteSigner;
+use token::signer::{KeySigner, Signer, WalletSigner};
+
+/// The private key for the test account.
+const PRIV_KEY: &str = "128A3D2146A69581FD8FC4C0A9B7A96A5755D85255D4E47F814AFA69D7726C8D";
+
+#[test]
+fn key_and_wallet_signers_agree() {
+    let key = SecretKey::from_slice(&hex::decode(PRIV_KEY).unwrap()).unwrap();
+    let mut wallet = essential_wallet::Wallet::temp().unwrap();
+    wallet
+        .insert_key("alice", essential_signer::Key::Secp256k1(key))
+        .unwrap();
+    let mut wallet = WalletSigner::new(wallet, "alice");
+    let mut key = KeySigner::new(key);
+
+    let words = [1, 2, 3, 4, 5];
+    let hashed_key = key.hashed_key().unwrap();
+    assert_eq!(hashed_key, wallet.hashed_key().unwrap());
+
+    for signer in [&mut wallet as &mut dyn Signer, &mut key] {
+        let sig = signer.sign_words(&words).unwrap();
+        token::verify::verify_key(&words, &sig, hashed_key).unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_signer_over_socket() {
+    let dir = std::env::temp_dir().join(format!("token-signer-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket = dir.join("signer.sock");
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+    let key = SecretKey::from_slice(&hex::decode(PRIV_KEY).unwrap()).unwrap();
+
+    // A minimal signing service that answers two requests.
+    let service = std::thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let mut stream = stream.unwrap();
+            let mut request = String::new();
+            BufReader::new(&stream).read_line(&mut request).unwrap();
+            let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+            let secp = Secp256k1::new();
+            let response = match request["method"].as_str().unwrap() {
+                "public_key" => serde_json::json!({
+                    "public_key": hex::encode(key.public_key(&secp).serialize()),
+                }),
+                "sign_hash" => {
+                    let hash: [u8; 32] = hex::decode(request["hash"].as_str().unwrap())
+                        .unwrap()
+                        .try_into()
+                        .unwrap();
+                    let message = essential_sign::secp256k1::Message::from_digest(hash);
+                    let (id, sig) = secp
+                        .sign_ecdsa_recoverable(&message, &key)
+                        .serialize_compact();
+                    serde_json::json!({
+                        "signature": hex::encode(sig),
+                        "recovery_id": id.to_i32(),
+                    })
+                }
+                method => panic!("Unexpected method {}", method),
+            };
+            writeln!(stream, "{}", response).unwrap();
+        }
+    });
+
+    let mut remote = RemoteSigner::new(&socket);
+    let words = [1, 2, 3, 4, 5];
+    let hashed_key = remote.hashed_key().unwrap();
+    let sig = remote.sign_words(&words).unwrap();
+    token::verify::verify_key(&words, &sig, hashed_key).unwrap();
+    assert_eq!(hashed_key, KeySigner::new(key).hashed_key().unwrap());
+
+    service.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
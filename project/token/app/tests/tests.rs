@@ -80,6 +80,7 @@ async fn mint_and_transfer() {
 
     // Build the mint solution
     let build_solution = token::mint::BuildSolution {
+        token: token::token::ADDRESS,
         new_nonce: to_sign.new_nonce,
         current_balance: Query(balance),
         hashed_key: alice_hashed_key,
@@ -129,7 +130,7 @@ async fn mint_and_transfer() {
         hashed_from_key: alice_hashed_key,
         hashed_to_key: bob_hashed_key,
         amount: 500,
-        nonce: Query(nonce),
+        nonce: Query(nonce.clone()),
     };
 
     // Sign the transfer solution
@@ -153,16 +154,29 @@ async fn mint_and_transfer() {
 
     // Build the transfer solution
     let solution = token::transfer::BuildSolution {
+        token: token::token::ADDRESS,
         hashed_from_key: alice_hashed_key,
         hashed_to_key: bob_hashed_key,
         new_nonce: to_sign.new_nonce,
         amount: 500,
+        current_nonce: Query(nonce),
         current_from_balance: Query(from_balance),
         current_to_balance: Query(to_balance),
         signature: sig,
     };
     let solution = token::transfer::build_solution(solution).unwrap();
 
+    // Decode the transfer solution
+    let [token::decode::Decoded::Transfer(decoded)] =
+        &token::decode::solution(&solution, &token::token::ADDRESS).unwrap()[..]
+    else {
+        panic!("Expected a single transfer")
+    };
+    assert_eq!(decoded.key, alice_hashed_key);
+    assert_eq!(decoded.to, bob_hashed_key);
+    assert_eq!(decoded.amount, 500);
+    assert_eq!(decoded.extra, token::decode::ExtraConstraints::None);
+
     // Submit the transfer solution
     utils::builder::submit(&dbs.builder, solution.clone())
         .await
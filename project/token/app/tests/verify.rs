@@ -0,0 +1,134 @@
+use essential_signer::Signature;
+use essential_types::Word;
+use essential_wallet::Wallet;
+use token::Query;
+
+/// The private key for the test account.
+const PRIV_KEY: &str = "128A3D2146A69581FD8FC4C0A9B7A96A5755D85255D4E47F814AFA69D7726C8D";
+
+#[test]
+fn transfer_signature_is_checked_locally() {
+    let (mut wallet, alice_hashed_key) = wallet_with_alice();
+    let bob_hashed_key = [1, 2, 3, 4];
+
+    // Sign a transfer from Alice to Bob
+    let init = token::transfer::Init {
+        hashed_from_key: alice_hashed_key,
+        hashed_to_key: bob_hashed_key,
+        amount: 500,
+        nonce: Query(None),
+    };
+    let to_sign = token::transfer::data_to_sign(init).unwrap();
+    let sig = sign(&mut wallet, &to_sign.to_words());
+    token::verify::transfer(&to_sign, &sig).unwrap();
+
+    let build = |new_nonce, amount| token::transfer::BuildSolution {
+        token: token::token::ADDRESS,
+        hashed_from_key: alice_hashed_key,
+        hashed_to_key: bob_hashed_key,
+        new_nonce,
+        amount,
+        current_nonce: Query(None),
+        current_from_balance: Query(Some(vec![1000])),
+        current_to_balance: Query(None),
+        signature: sig,
+    };
+
+    // The signed data builds
+    token::transfer::build_solution(build(to_sign.new_nonce, 500)).unwrap();
+
+    // A different nonce or amount than was signed is rejected
+    assert!(token::transfer::build_solution(build(to_sign.new_nonce + 1, 500)).is_err());
+    assert!(token::transfer::build_solution(build(to_sign.new_nonce, 501)).is_err());
+}
+
+#[test]
+fn signed_nonce_must_follow_the_queried_nonce() {
+    let (mut wallet, alice_hashed_key) = wallet_with_alice();
+
+    // Alice signs as if her nonce were 5, but the node has no nonce for her
+    let init = token::burn::Init {
+        hashed_key: alice_hashed_key,
+        amount: 10,
+        nonce: Query(Some(vec![5])),
+    };
+    let to_sign = token::burn::data_to_sign(init).unwrap();
+    let build = token::burn::BuildSolution {
+        token: token::token::ADDRESS,
+        new_nonce: to_sign.new_nonce,
+        current_nonce: Query(None),
+        current_balance: Query(Some(vec![1000])),
+        hashed_key: alice_hashed_key,
+        amount: 10,
+        signature: sign(&mut wallet, &to_sign.to_words()),
+    };
+    let err = token::burn::build_solution(build).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<token::Error>(),
+        Some(&token::Error::Unsatisfied("@safe_increment(nonce)"))
+    );
+}
+
+#[test]
+fn burn_signature_by_other_key_is_rejected() {
+    let (mut wallet, alice_hashed_key) = wallet_with_alice();
+    wallet
+        .new_key_pair("bob", essential_wallet::Scheme::Secp256k1)
+        .unwrap();
+
+    // Bob signs a burn of Alice's tokens
+    let init = token::burn::Init {
+        hashed_key: alice_hashed_key,
+        amount: 10,
+        nonce: Query(None),
+    };
+    let to_sign = token::burn::data_to_sign(init).unwrap();
+    let sig = wallet.sign_words(&to_sign.to_words(), "bob").unwrap();
+    let Signature::Secp256k1(sig) = sig else {
+        panic!("Invalid signature")
+    };
+    assert!(token::verify::burn(&to_sign, &sig).is_err());
+
+    let build = token::burn::BuildSolution {
+        token: token::token::ADDRESS,
+        new_nonce: to_sign.new_nonce,
+        current_nonce: Query(None),
+        current_balance: Query(Some(vec![1000])),
+        hashed_key: alice_hashed_key,
+        amount: 10,
+        signature: sig,
+    };
+    assert!(token::burn::build_solution(build).is_err());
+}
+
+// Helper function to create a wallet holding Alice's key
+fn wallet_with_alice() -> (Wallet, [Word; 4]) {
+    let mut wallet = essential_wallet::Wallet::temp().unwrap();
+    let key = hex::decode(PRIV_KEY).unwrap();
+    wallet
+        .insert_key(
+            "alice",
+            essential_signer::Key::Secp256k1(
+                essential_signer::secp256k1::SecretKey::from_slice(&key).unwrap(),
+            ),
+        )
+        .unwrap();
+    let essential_signer::PublicKey::Secp256k1(public_key) =
+        wallet.get_public_key("alice").unwrap()
+    else {
+        panic!("Invalid public key")
+    };
+    let hashed_key = token::verify::hash_public_key(&public_key);
+    (wallet, hashed_key)
+}
+
+// Helper function to sign words as Alice
+fn sign(
+    wallet: &mut Wallet,
+    words: &[Word],
+) -> essential_sign::secp256k1::ecdsa::RecoverableSignature {
+    let Signature::Secp256k1(sig) = wallet.sign_words(words, "alice").unwrap() else {
+        panic!("Invalid signature")
+    };
+    sig
+}